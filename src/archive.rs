@@ -0,0 +1,170 @@
+use crate::is_media_file;
+use chrono::{Local, NaiveDate, TimeZone};
+use flate2::read::GzDecoder;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::SystemTime;
+use tempfile::TempDir;
+
+// Whether a source is an archive to import from instead of a folder
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    path.is_file()
+        && [".zip", ".tar", ".tar.gz", ".tgz"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+}
+
+// Extract the media files of a zip or tar archive into a staging folder,
+// keeping their folders and modification times for the date strategies.
+// The archive itself is only read.
+pub fn extract_media(archive: &Path) -> Result<TempDir, Box<dyn Error>> {
+    let staging = TempDir::new()?;
+    info!(
+        "Extracting media of {} into {}",
+        archive.display(),
+        staging.path().display()
+    );
+    let name = archive.to_string_lossy().to_lowercase();
+    let file = File::open(archive)?;
+    let extracted = if name.ends_with(".zip") {
+        extract_zip(file, staging.path())?
+    } else if name.ends_with(".tar") {
+        extract_tar(file, staging.path())?
+    } else {
+        extract_tar(GzDecoder::new(file), staging.path())?
+    };
+    info!("Extracted {} media files", extracted);
+    Ok(staging)
+}
+
+fn extract_zip(file: File, staging: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut zip = zip::ZipArchive::new(file)?;
+    let mut extracted = 0;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        // enclosed_name refuses entries escaping the staging folder
+        let Some(name) = entry.enclosed_name() else {
+            warn!(
+                "Skipping archive entry {} with an unsafe path",
+                entry.name()?
+            );
+            continue;
+        };
+        if entry.is_dir() || !is_media_file(&name) {
+            continue;
+        }
+        let path = staging.join(&name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out = File::create(&path)?;
+        io::copy(&mut entry, &mut out)?;
+        // zip times are local and without a timezone
+        let modified = entry.last_modified().and_then(|time| {
+            NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?
+                .and_hms_opt(
+                    time.hour().into(),
+                    time.minute().into(),
+                    time.second().into(),
+                )
+                .and_then(|time| Local.from_local_datetime(&time).earliest())
+        });
+        if let Some(modified) = modified {
+            out.set_modified(SystemTime::from(modified))?;
+        }
+        extracted += 1;
+    }
+    Ok(extracted)
+}
+
+fn extract_tar(reader: impl Read, staging: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut tar = tar::Archive::new(reader);
+    tar.set_preserve_mtime(true);
+    let mut extracted = 0;
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if !entry.header().entry_type().is_file() || !is_media_file(&path) {
+            continue;
+        }
+        // unpack_in refuses entries escaping the staging folder
+        if entry.unpack_in(staging)? {
+            extracted += 1;
+        } else {
+            warn!(
+                "Skipping archive entry {} with an unsafe path",
+                path.display()
+            );
+        }
+    }
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Duration;
+    use zip::write::SimpleFileOptions;
+
+    fn modified(path: &Path) -> Duration {
+        fs::metadata(path)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+    }
+
+    #[test]
+    fn media_of_zip_is_extracted() {
+        let tmpdir = TempDir::new().unwrap();
+        let archive = tmpdir.path().join("backup.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("DCIM/IMG_20210130_000001.jpg", options)
+            .unwrap();
+        zip.write_all(b"image").unwrap();
+        zip.start_file("notes.txt", options).unwrap();
+        zip.write_all(b"text").unwrap();
+        zip.finish().unwrap();
+        assert!(is_archive(&archive));
+
+        let staging = extract_media(&archive).unwrap();
+        let image = staging.path().join("DCIM/IMG_20210130_000001.jpg");
+        assert_eq!(b"image".to_vec(), fs::read(image).unwrap());
+        assert!(!staging.path().join("notes.txt").exists());
+        assert!(archive.exists());
+
+        let target = tmpdir.path().join("target");
+        let mut mediaconfig =
+            crate::MediaConfig::new(staging.path().to_str().unwrap().to_string(), target.clone());
+        assert_eq!(1, mediaconfig.copy_media_files().unwrap().copied);
+        assert!(target.join("2021/01/30/IMG_20210130_000001.jpg").exists());
+    }
+
+    #[test]
+    fn media_of_tar_is_extracted() {
+        let tmpdir = TempDir::new().unwrap();
+        let archive = tmpdir.path().join("backup.tar");
+        let mut tar = tar::Builder::new(File::create(&archive).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mtime(1_600_000_000);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "scan.png", &b"image"[..])
+            .unwrap();
+        tar.finish().unwrap();
+        drop(tar);
+
+        let staging = extract_media(&archive).unwrap();
+        let image = staging.path().join("scan.png");
+        assert_eq!(b"image".to_vec(), fs::read(&image).unwrap());
+        assert_eq!(Duration::from_secs(1_600_000_000), modified(&image));
+    }
+}
@@ -0,0 +1,88 @@
+use chrono::NaiveDate;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// How much of a clip to search for the recording date
+const SEARCH_LIMIT: u64 = 4 * 1024 * 1024;
+
+// Tags of the modified DV pack holding the recording date and time
+const TAG_YEAR_MONTH: u8 = 0x18;
+const TAG_DAY_TIME: u8 = 0x19;
+
+// Read the recording date of an AVCHD clip (.mts/.m2ts). Camcorders write it
+// as BCD digits into the "MDPM" user data of the video stream, in local time.
+// This is best-effort, clips without one are left to the other strategies.
+pub fn read_avchd_date(filename: &str) -> Option<String> {
+    let mut head = Vec::new();
+    File::open(Path::new(filename))
+        .ok()?
+        .take(SEARCH_LIMIT)
+        .read_to_end(&mut head)
+        .ok()?;
+    find_date(&head)
+}
+
+fn find_date(data: &[u8]) -> Option<String> {
+    let mut rest = data;
+    while let Some(start) = rest.windows(4).position(|window| window == b"MDPM") {
+        rest = &rest[start + 4..];
+        if let Some(date) = pack_date(rest) {
+            return Some(date);
+        }
+    }
+    None
+}
+
+// Parse the entries following an MDPM marker: a count, then a tag and four
+// value bytes per entry
+fn pack_date(pack: &[u8]) -> Option<String> {
+    let count = *pack.first()? as usize;
+    let (mut year, mut month, mut day) = (None, None, None);
+    for entry in pack.get(1..1 + count * 5)?.chunks_exact(5) {
+        match entry[0] {
+            TAG_YEAR_MONTH => {
+                year = Some(bcd(entry[2])? * 100 + bcd(entry[3])?);
+                month = Some(bcd(entry[4])?);
+            }
+            TAG_DAY_TIME => day = Some(bcd(entry[1])?),
+            _ => {}
+        }
+    }
+    let date = NaiveDate::from_ymd_opt(year? as i32, month?, day?)?;
+    Some(date.format("%Y/%m/%d").to_string())
+}
+
+fn bcd(byte: u8) -> Option<u32> {
+    let (high, low) = (byte >> 4, byte & 0x0f);
+    (high < 10 && low < 10).then_some((high * 10 + low) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An MDPM pack recorded on 2009-08-15 at 14:30:05
+    fn pack() -> Vec<u8> {
+        let mut pack = b"\x47\x01\x00MDPM".to_vec();
+        pack.push(3);
+        pack.extend_from_slice(&[TAG_YEAR_MONTH, 0x00, 0x20, 0x09, 0x08]);
+        pack.extend_from_slice(&[TAG_DAY_TIME, 0x15, 0x14, 0x30, 0x05]);
+        pack.extend_from_slice(&[0x70, 0x00, 0x00, 0x00, 0x00]);
+        pack
+    }
+
+    #[test]
+    fn date_from_mdpm_pack() {
+        assert_eq!(Some(String::from("2009/08/15")), find_date(&pack()));
+    }
+
+    #[test]
+    fn invalid_packs_are_ignored() {
+        let mut data = b"MDPM\x01\x18\x00\x20\xaa\x01".to_vec();
+        data.extend(pack());
+        assert_eq!(Some(String::from("2009/08/15")), find_date(&data));
+        assert_eq!(None, find_date(b"no metadata at all"));
+        assert_eq!(None, find_date(b"MDPM\x02\x18"));
+    }
+}
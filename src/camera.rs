@@ -0,0 +1,42 @@
+use std::error::Error;
+use tempfile::TempDir;
+
+// Download all media of a connected camera or phone into a staging folder,
+// which the caller sorts like any other source. This shells out to gphoto2,
+// which speaks PTP and MTP; how well it works depends on the device and
+// platform.
+#[cfg(feature = "gphoto2")]
+pub fn download() -> Result<TempDir, Box<dyn Error>> {
+    use std::io;
+    use std::process::Command;
+
+    let staging = TempDir::new()?;
+    info!("Downloading from camera into {}", staging.path().display());
+    let status = Command::new("gphoto2")
+        .args(["--get-all-files", "--skip-existing"])
+        .current_dir(staging.path())
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => "gphoto2 is not installed or not on the PATH".into(),
+            _ => Box::<dyn Error>::from(format!("could not run gphoto2: {}", e)),
+        })?;
+    if !status.success() {
+        return Err(format!("gphoto2 failed ({}), is a camera connected?", status).into());
+    }
+    Ok(staging)
+}
+
+#[cfg(not(feature = "gphoto2"))]
+pub fn download() -> Result<TempDir, Box<dyn Error>> {
+    Err("importing from cameras needs a build with the gphoto2 feature".into())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "gphoto2"))]
+    #[test]
+    fn download_needs_feature() {
+        let error = super::download().unwrap_err();
+        assert!(error.to_string().contains("gphoto2 feature"));
+    }
+}
@@ -0,0 +1,36 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::checksum::{self, ChecksumAlgo};
+
+// Where the content of a file goes in a content-addressed store, sharded by
+// the first two bytes of its hash, e.g. ab/cd/abcd1234....jpg
+pub fn object_path(store: &Path, source: &Path, algo: ChecksumAlgo) -> io::Result<PathBuf> {
+    let hash = checksum::hex_digest(source, algo)?;
+    let mut name = hash.clone();
+    if let Some(ext) = source.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy().to_lowercase());
+    }
+    Ok(store.join(&hash[..2]).join(&hash[2..4]).join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn objects_are_sharded_by_hash() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_1.JPG");
+        fs::write(&source, b"image").unwrap();
+        // sha256 of "image"
+        let hash = "6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d";
+        assert_eq!(
+            Path::new("/store/61/05").join(format!("{}.jpg", hash)),
+            object_path(Path::new("/store"), &source, ChecksumAlgo::Sha256).unwrap()
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::checksum::ChecksumAlgo;
+use crate::DateSource;
+
+/// A copied file to record in the catalog
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub source: String,
+    pub target: PathBuf,
+    /// The date the file was sorted by, like 2021-01-30, or shorter for a year or month
+    pub date: Option<String>,
+    pub date_source: Option<DateSource>,
+}
+
+/// The catalog holds one row per copy, keyed by its path, so a re-run that
+/// copies a file again updates its row
+#[cfg(feature = "catalog")]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS media (
+    target       TEXT PRIMARY KEY NOT NULL,
+    source       TEXT NOT NULL,
+    date         TEXT,
+    date_source  TEXT,
+    size         INTEGER NOT NULL,
+    hash         TEXT NOT NULL,
+    hash_algo    TEXT NOT NULL,
+    make         TEXT,
+    model        TEXT,
+    cataloged_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS media_date ON media (date);
+";
+
+// Fail early when the catalog cannot be written, instead of after copying
+pub fn ensure_supported() -> Result<(), Box<dyn Error>> {
+    if cfg!(feature = "catalog") {
+        Ok(())
+    } else {
+        Err("--catalog needs a build with the catalog feature".into())
+    }
+}
+
+// Record the copied files in the catalog, creating it if needed
+#[cfg(feature = "catalog")]
+pub fn record(
+    database: &Path,
+    entries: &[Entry],
+    algo: ChecksumAlgo,
+    mmap_exif: bool,
+) -> Result<(), Box<dyn Error>> {
+    use clap::ValueEnum;
+    use exif::Tag;
+    use rusqlite::{params, Connection};
+
+    let mut connection = Connection::open(database)?;
+    connection.execute_batch(SCHEMA)?;
+    let transaction = connection.transaction()?;
+    let cataloged_at = chrono::Local::now().to_rfc3339();
+    let algo_name = algo.to_possible_value().unwrap().get_name().to_string();
+    {
+        let mut upsert = transaction.prepare(
+            "INSERT INTO media
+                 (target, source, date, date_source, size, hash, hash_algo, make, model, cataloged_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT (target) DO UPDATE SET
+                 source = excluded.source,
+                 date = excluded.date,
+                 date_source = excluded.date_source,
+                 size = excluded.size,
+                 hash = excluded.hash,
+                 hash_algo = excluded.hash_algo,
+                 make = excluded.make,
+                 model = excluded.model,
+                 cataloged_at = excluded.cataloged_at",
+        )?;
+        for entry in entries {
+            let target = entry.target.to_string_lossy();
+            let size = i64::try_from(std::fs::metadata(&entry.target)?.len())?;
+            let hash = crate::checksum::hex_digest(&entry.target, algo)?;
+            let exif = crate::open_exif(&target, mmap_exif);
+            let field = |tag| exif.as_ref().and_then(|exif| crate::ascii_field(exif, tag));
+            upsert.execute(params![
+                target,
+                entry.source,
+                entry.date,
+                entry.date_source.map(|source| source.to_string()),
+                size,
+                hash,
+                algo_name,
+                field(Tag::Make),
+                field(Tag::Model),
+                cataloged_at,
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    info!(
+        "Recorded {} files in the catalog {}",
+        entries.len(),
+        database.display()
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "catalog"))]
+pub fn record(
+    _database: &Path,
+    _entries: &[Entry],
+    _algo: ChecksumAlgo,
+    _mmap_exif: bool,
+) -> Result<(), Box<dyn Error>> {
+    ensure_supported()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "catalog"))]
+    #[test]
+    fn catalog_needs_feature() {
+        let error = ensure_supported().unwrap_err();
+        assert!(error.to_string().contains("catalog feature"));
+    }
+
+    #[cfg(feature = "catalog")]
+    #[test]
+    fn reruns_update_rows() {
+        use rusqlite::Connection;
+        use std::fs;
+        use tempfile::TempDir;
+
+        let tmpdir = TempDir::new().unwrap();
+        let database = tmpdir.path().join("catalog.db");
+        let target = tmpdir.path().join("IMG_1.jpg");
+        fs::write(&target, b"image").unwrap();
+        let mut entry = Entry {
+            source: String::from("/card/IMG_1.jpg"),
+            target: target.clone(),
+            date: Some(String::from("2021-01-30")),
+            date_source: Some(DateSource::Exif),
+        };
+        record(&database, &[entry.clone()], ChecksumAlgo::Sha256, false).unwrap();
+        entry.date = Some(String::from("2021-01-31"));
+        record(&database, &[entry], ChecksumAlgo::Sha256, false).unwrap();
+
+        let connection = Connection::open(&database).unwrap();
+        let (count, date, hash): (i64, String, String) = connection
+            .query_row(
+                "SELECT COUNT(*), MAX(date), MAX(hash) FROM media",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(1, count);
+        assert_eq!("2021-01-31", date);
+        assert_eq!(
+            "6105d6cc76af400325e94d588ce511be5bfdbb73b437dc51eca43917d7a43e3d",
+            hash
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use clap::ValueEnum;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Hash used to verify copies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ChecksumAlgo {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+// Hash the content of a file
+pub fn file_digest(path: &Path, algo: ChecksumAlgo) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    match algo {
+        ChecksumAlgo::Sha256 => digest::<Sha256>(&mut file),
+        ChecksumAlgo::Sha512 => digest::<Sha512>(&mut file),
+    }
+}
+
+// The hash of a file as lowercase hex, as printed by sha256sum
+pub fn hex_digest(path: &Path, algo: ChecksumAlgo) -> io::Result<String> {
+    Ok(file_digest(path, algo)?
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+// Read a list of hex hashes, one per line. Like in the output of sha256sum
+// anything after the hash is ignored, so such files can be used as they are.
+pub fn read_hash_list(path: &Path) -> io::Result<HashSet<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|hash| hash.to_lowercase())
+        .collect())
+}
+
+// Hash content held in memory
+pub fn bytes_digest(mut bytes: &[u8], algo: ChecksumAlgo) -> Vec<u8> {
+    let hash = match algo {
+        ChecksumAlgo::Sha256 => digest::<Sha256>(&mut bytes),
+        ChecksumAlgo::Sha512 => digest::<Sha512>(&mut bytes),
+    };
+    hash.unwrap()
+}
+
+fn digest<D: Digest>(file: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+// Check the copy has the content of its source, removing it otherwise
+pub fn verify_copy(from: &Path, to: &Path, algo: ChecksumAlgo) -> Result<(), Box<dyn Error>> {
+    if file_digest(from, algo)? != file_digest(to, algo)? {
+        error!("Checksum mismatch for {}", to.display());
+        fs::remove_file(to)?;
+        return Err(format!("copy of {} does not match its source", from.display()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sha256_of_file() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("file");
+        fs::write(&path, b"abc").unwrap();
+        let digest = file_digest(&path, ChecksumAlgo::Sha256).unwrap();
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            digest
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+        assert_eq!(64, file_digest(&path, ChecksumAlgo::Sha512).unwrap().len());
+    }
+
+    #[test]
+    fn mismatching_copy_is_removed() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let copy = tmpdir.path().join("copy");
+        fs::write(&source, b"original").unwrap();
+        fs::write(&copy, b"origina!").unwrap();
+
+        assert!(verify_copy(&source, &copy, ChecksumAlgo::Sha256).is_err());
+        assert!(!copy.exists());
+
+        fs::copy(&source, &copy).unwrap();
+        assert!(verify_copy(&source, &copy, ChecksumAlgo::Sha256).is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+use crate::contains_media;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// All folders to scan below and including the root, in a stable order
+pub fn folders(root: &Path, recursive: bool, skip_empty_dirs: bool) -> io::Result<Vec<PathBuf>> {
+    let mut folders = vec![root.to_path_buf()];
+    let mut pending = if recursive {
+        vec![root.to_path_buf()]
+    } else {
+        Vec::new()
+    };
+    while let Some(folder) = pending.pop() {
+        for entry in fs::read_dir(&folder)? {
+            let path = entry?.path();
+            if !path.is_dir() || (skip_empty_dirs && !contains_media(&path)) {
+                continue;
+            }
+            folders.push(path.clone());
+            pending.push(path);
+        }
+    }
+    folders.sort();
+    Ok(folders)
+}
+
+// The last folder a previous run finished, if there was one
+pub fn read_cursor(cursor: &Path) -> io::Result<Option<PathBuf>> {
+    match fs::read_to_string(cursor) {
+        Ok(folder) if !folder.trim().is_empty() => Ok(Some(PathBuf::from(folder.trim_end()))),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_cursor(cursor: &Path, folder: &Path) -> io::Result<()> {
+    fs::write(cursor, folder.to_string_lossy().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn folders_are_sorted() {
+        let tmpdir = TempDir::new().unwrap();
+        let root = tmpdir.path();
+        for folder in ["b", "a/2", "a/1"] {
+            fs::create_dir_all(root.join(folder)).unwrap();
+        }
+        let mut expected = vec![root.to_path_buf()];
+        expected.extend(
+            ["a", "a/1", "a/2", "b"]
+                .iter()
+                .map(|folder| root.join(folder)),
+        );
+        assert_eq!(expected, folders(root, true, false).unwrap());
+        assert_eq!(
+            vec![root.to_path_buf()],
+            folders(root, false, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn cursor_roundtrip() {
+        let tmpdir = TempDir::new().unwrap();
+        let cursor = tmpdir.path().join("cursor");
+        assert_eq!(None, read_cursor(&cursor).unwrap());
+        write_cursor(&cursor, Path::new("/photos/2021")).unwrap();
+        assert_eq!(
+            Some(PathBuf::from("/photos/2021")),
+            read_cursor(&cursor).unwrap()
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// What to do when a target file already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep the existing file and skip the new one
+    Skip,
+    /// Copy the new file under a free name like IMG_0001_2.jpg, unless both are identical
+    #[default]
+    Rename,
+    /// Replace the existing file, unless both are identical
+    Overwrite,
+    /// Replace the existing file if its size differs, skip the new one otherwise
+    OverwriteIfDifferentSize,
+}
+
+impl ConflictPolicy {
+    // Whether an existing target gets replaced by its source
+    pub fn overwrites(self, source: &Path, target: &Path) -> bool {
+        match self {
+            ConflictPolicy::Overwrite => true,
+            ConflictPolicy::OverwriteIfDifferentSize => {
+                match (fs::metadata(source), fs::metadata(target)) {
+                    (Ok(source), Ok(target)) => source.len() != target.len(),
+                    _ => false,
+                }
+            }
+            ConflictPolicy::Skip | ConflictPolicy::Rename => false,
+        }
+    }
+}
+
+// Find a free name by appending _2, _3, ... to the file stem
+pub fn free_name(target: &Path, taken: &HashSet<PathBuf>) -> PathBuf {
+    let (stem, extension) = stem_and_extension(target);
+    (2..)
+        .map(|n| target.with_file_name(format!("{}_{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists() && !taken.contains(candidate))
+        .unwrap()
+}
+
+// The files an earlier rename may have put next to the target, named like
+// free_name does, in no particular order
+pub fn renamed_siblings(target: &Path) -> Vec<PathBuf> {
+    let (stem, extension) = stem_and_extension(target);
+    let prefix = format!("{}_", stem);
+    let Some(entries) = target.parent().and_then(|folder| fs::read_dir(folder).ok()) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(&extension))
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+fn stem_and_extension(target: &Path) -> (String, String) {
+    let stem = target
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (stem, extension)
+}
+
+/// Audit trail of targets renamed because of a collision
+#[derive(Debug, Default)]
+pub struct CollisionLog {
+    file: Option<File>,
+}
+
+impl CollisionLog {
+    // Log to the given file as well, appending to earlier runs
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Some(file) })
+    }
+
+    pub fn record(
+        &mut self,
+        source: &str,
+        original: &Path,
+        renamed: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        info!(
+            "Target {} exists, renamed to {} for {}",
+            original.display(),
+            renamed.display(),
+            source
+        );
+        if let Some(file) = &mut self.file {
+            writeln!(
+                file,
+                "{}\t{} -> {}",
+                source,
+                original.display(),
+                renamed.display()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn overwrite_if_different_size() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source.jpg");
+        let target = tmpdir.path().join("target.jpg");
+        fs::write(&source, b"re-export").unwrap();
+        fs::write(&target, b"original!").unwrap();
+        let policy = ConflictPolicy::OverwriteIfDifferentSize;
+        assert!(!policy.overwrites(&source, &target));
+
+        fs::write(&target, b"original").unwrap();
+        assert!(policy.overwrites(&source, &target));
+        assert!(ConflictPolicy::Overwrite.overwrites(&source, &target));
+        assert!(!ConflictPolicy::Skip.overwrites(&source, &target));
+    }
+
+    #[test]
+    fn free_name_skips_existing_and_taken() {
+        let tmpdir = TempDir::new().unwrap();
+        let target = tmpdir.path().join("IMG_0001.jpg");
+        fs::File::create(&target).unwrap();
+        fs::File::create(tmpdir.path().join("IMG_0001_2.jpg")).unwrap();
+        let taken = HashSet::from([tmpdir.path().join("IMG_0001_3.jpg")]);
+
+        assert_eq!(
+            tmpdir.path().join("IMG_0001_4.jpg"),
+            free_name(&target, &taken)
+        );
+    }
+
+    #[test]
+    fn renamed_siblings_match_free_names() {
+        let tmpdir = TempDir::new().unwrap();
+        let target = tmpdir.path().join("IMG_0001.jpg");
+        for name in [
+            "IMG_0001.jpg",
+            "IMG_0001_2.jpg",
+            "IMG_0001_13.jpg",
+            "IMG_0001_2.png",
+            "IMG_0001_x.jpg",
+            "IMG_0001_.jpg",
+            "IMG_00011.jpg",
+        ] {
+            fs::File::create(tmpdir.path().join(name)).unwrap();
+        }
+
+        let mut siblings = renamed_siblings(&target);
+        siblings.sort();
+        assert_eq!(
+            vec![
+                tmpdir.path().join("IMG_0001_13.jpg"),
+                tmpdir.path().join("IMG_0001_2.jpg")
+            ],
+            siblings
+        );
+    }
+
+    #[test]
+    fn collision_log_records_source() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("collisions.log");
+        let mut log = CollisionLog::open(&path).unwrap();
+        log.record(
+            "/src/a/IMG_0001.jpg",
+            Path::new("/t/IMG_0001.jpg"),
+            Path::new("/t/IMG_0001_2.jpg"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "/src/a/IMG_0001.jpg\t/t/IMG_0001.jpg -> /t/IMG_0001_2.jpg\n",
+            fs::read_to_string(path).unwrap()
+        );
+    }
+}
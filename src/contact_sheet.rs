@@ -0,0 +1,164 @@
+use crate::{is_media_file, is_video_file};
+use image::{GenericImage, Rgb, RgbImage};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONTACT_SHEET_FILE: &str = "_contactsheet.jpg";
+
+// Size of a tile and how many of them go into a row. A sheet holds at most
+// SHEET_ROWS rows, further images go to _contactsheet_2.jpg and so on, which
+// keeps every sheet well below the 65535 pixels a JPEG can be high.
+const TILE_SIZE: u32 = 160;
+const COLUMNS: u32 = 6;
+const SHEET_ROWS: u32 = 40;
+
+// Tile thumbnails of all images in each folder that got new copies into
+// contact sheets in that folder. Videos and images that cannot be decoded are
+// left out. A sheet that cannot be written is logged, it does not stop the run.
+pub fn write_contact_sheets(copied: &[(String, PathBuf)]) {
+    let folders: BTreeSet<&Path> = copied
+        .iter()
+        .filter_map(|(_, target)| target.parent())
+        .collect();
+    for folder in folders {
+        if let Err(e) = write_folder_sheets(folder) {
+            warn!(
+                "Could not write the contact sheet of {}: {}",
+                folder.display(),
+                e
+            );
+        }
+    }
+}
+
+// Whether a file is one of the contact sheets, which are never sorted
+pub fn is_contact_sheet(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    name == CONTACT_SHEET_FILE
+        || name
+            .strip_prefix("_contactsheet_")
+            .and_then(|rest| rest.strip_suffix(".jpg"))
+            .is_some_and(|page| page.parse::<u32>().is_ok_and(|page| page >= 2))
+}
+
+fn sheet_path(folder: &Path, page: usize) -> PathBuf {
+    match page {
+        1 => folder.join(CONTACT_SHEET_FILE),
+        page => folder.join(format!("_contactsheet_{}.jpg", page)),
+    }
+}
+
+fn write_folder_sheets(folder: &Path) -> Result<(), Box<dyn Error>> {
+    let mut images = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_file()
+            && is_media_file(&path)
+            && !is_video_file(&path)
+            && !is_contact_sheet(&path)
+        {
+            images.push(path);
+        }
+    }
+    images.sort();
+    let thumbnails: Vec<RgbImage> = images.iter().filter_map(|path| thumbnail(path)).collect();
+    let mut pages = 0;
+    for (i, tiles) in thumbnails
+        .chunks((COLUMNS * SHEET_ROWS) as usize)
+        .enumerate()
+    {
+        let path = sheet_path(folder, i + 1);
+        contact_sheet(tiles).save(&path)?;
+        info!("Wrote contact sheet {}", path.display());
+        pages = i + 1;
+    }
+    // pages left over from a run when the folder had more images
+    let mut stale = pages + 1;
+    while sheet_path(folder, stale).exists() {
+        fs::remove_file(sheet_path(folder, stale))?;
+        stale += 1;
+    }
+    Ok(())
+}
+
+fn thumbnail(path: &Path) -> Option<RgbImage> {
+    match image::open(path) {
+        Ok(image) => Some(image.thumbnail(TILE_SIZE, TILE_SIZE).to_rgb8()),
+        Err(e) => {
+            debug!("Leaving {} out of the contact sheet: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn contact_sheet(thumbnails: &[RgbImage]) -> RgbImage {
+    let columns = COLUMNS.min(thumbnails.len() as u32);
+    let rows = (thumbnails.len() as u32).div_ceil(COLUMNS);
+    let mut sheet = RgbImage::from_pixel(columns * TILE_SIZE, rows * TILE_SIZE, Rgb([32, 32, 32]));
+    for (i, thumbnail) in thumbnails.iter().enumerate() {
+        let (column, row) = (i as u32 % COLUMNS, i as u32 / COLUMNS);
+        // center each thumbnail within its tile
+        let x = column * TILE_SIZE + (TILE_SIZE - thumbnail.width()) / 2;
+        let y = row * TILE_SIZE + (TILE_SIZE - thumbnail.height()) / 2;
+        // every thumbnail fits into its tile
+        sheet.copy_from(thumbnail, x, y).unwrap();
+    }
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sheet_tiles_copied_images() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut copied = Vec::new();
+        for name in ["a.png", "b.png"] {
+            let path = tmpdir.path().join(name);
+            RgbImage::new(400, 300).save(&path).unwrap();
+            copied.push((String::from(name), path));
+        }
+        let video = tmpdir.path().join("clip.mp4");
+        fs::write(&video, b"not an image").unwrap();
+        copied.push((String::from("clip.mp4"), video));
+
+        write_contact_sheets(&copied);
+        let sheet = image::open(tmpdir.path().join(CONTACT_SHEET_FILE)).unwrap();
+        assert_eq!((2 * TILE_SIZE, TILE_SIZE), (sheet.width(), sheet.height()));
+
+        // a later run with one more image tiles all of them
+        let path = tmpdir.path().join("c.png");
+        RgbImage::new(400, 300).save(&path).unwrap();
+        write_contact_sheets(&[(String::from("c.png"), path)]);
+        let sheet = image::open(tmpdir.path().join(CONTACT_SHEET_FILE)).unwrap();
+        assert_eq!((3 * TILE_SIZE, TILE_SIZE), (sheet.width(), sheet.height()));
+    }
+
+    #[test]
+    fn large_folders_get_several_sheets() {
+        let tmpdir = TempDir::new().unwrap();
+        let per_sheet = (COLUMNS * SHEET_ROWS) as usize;
+        let mut copied = Vec::new();
+        for i in 0..per_sheet + 1 {
+            let path = tmpdir.path().join(format!("{:04}.png", i));
+            RgbImage::new(8, 8).save(&path).unwrap();
+            copied.push((String::new(), path));
+        }
+        fs::write(tmpdir.path().join("_contactsheet_3.jpg"), b"stale").unwrap();
+
+        write_contact_sheets(&copied);
+        let first = image::open(tmpdir.path().join(CONTACT_SHEET_FILE)).unwrap();
+        assert_eq!(SHEET_ROWS * TILE_SIZE, first.height());
+        let second = image::open(tmpdir.path().join("_contactsheet_2.jpg")).unwrap();
+        assert_eq!((TILE_SIZE, TILE_SIZE), (second.width(), second.height()));
+        assert!(!tmpdir.path().join("_contactsheet_3.jpg").exists());
+        assert!(is_contact_sheet(Path::new("2021/_contactsheet_2.jpg")));
+        assert!(!is_contact_sheet(Path::new("2021/_contactsheet_x.jpg")));
+    }
+}
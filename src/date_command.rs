@@ -0,0 +1,126 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running date command is checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Run an external resolver with the path of a file appended to its arguments
+// and read an ISO date from the first line of its output. The command line is
+// split on whitespace, without any shell quoting. A failing, silent or slow
+// command only means that there is no date.
+pub fn resolve(command: &str, path: &Path, timeout: Duration) -> Option<NaiveDate> {
+    let mut words = command.split_whitespace();
+    let program = words.next()?;
+    let mut child = match Command::new(program)
+        .args(words)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Could not run --date-command {}: {}", program, e);
+            return None;
+        }
+    };
+    // read in the background, a command filling the pipe would never exit
+    let mut stdout = child.stdout.take()?;
+    let output = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() < timeout => thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                warn!(
+                    "--date-command took longer than {:?} for {}, skipping it",
+                    timeout,
+                    path.display()
+                );
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            Err(e) => {
+                warn!("Could not wait for --date-command: {}", e);
+                return None;
+            }
+        }
+    };
+    if !status.success() {
+        debug!("--date-command failed for {} ({})", path.display(), status);
+        return None;
+    }
+    let output = output.join().ok()?.ok()?;
+    let date = parse_date(&output);
+    if date.is_none() {
+        debug!(
+            "--date-command printed no date for {}: {}",
+            path.display(),
+            output.trim()
+        );
+    }
+    date
+}
+
+// The date of the first line, a full RFC 3339 timestamp keeps the date of its offset
+fn parse_date(output: &str) -> Option<NaiveDate> {
+    let line = output.lines().next()?.trim();
+    DateTime::parse_from_rfc3339(line)
+        .map(|datetime| datetime.date_naive())
+        .or_else(|_| NaiveDateTime::parse_from_str(line, "%Y-%m-%dT%H:%M:%S").map(|d| d.date()))
+        .or_else(|_| NaiveDateTime::parse_from_str(line, "%Y-%m-%d %H:%M:%S").map(|d| d.date()))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(line.split_whitespace().next()?, "%Y-%m-%d").ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_parsed_from_iso_output() {
+        let date = NaiveDate::from_ymd_opt(2021, 3, 4);
+        assert_eq!(date, parse_date("2021-03-04\n"));
+        assert_eq!(date, parse_date("2021-03-04T23:30:00\n"));
+        assert_eq!(date, parse_date("2021-03-04 23:30:00"));
+        assert_eq!(date, parse_date("2021-03-04T23:30:00+09:00"));
+        assert_eq!(date, parse_date("2021-03-04 /photos/a.heic\nmore"));
+        assert_eq!(None, parse_date("unknown"));
+        assert_eq!(None, parse_date(""));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_and_slow_commands_are_skipped() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmpdir = tempfile::TempDir::new().unwrap();
+        let file = tmpdir.path().join("a.heic");
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2021, 3, 4),
+            resolve("echo 2021-03-04", &file, timeout)
+        );
+        assert_eq!(None, resolve("false", &file, timeout));
+        assert_eq!(None, resolve("/nonexistent/resolver", &file, timeout));
+
+        let slow = tmpdir.path().join("slow.sh");
+        fs::write(&slow, "#!/bin/sh\nsleep 5\necho 2021-03-04\n").unwrap();
+        fs::set_permissions(&slow, fs::Permissions::from_mode(0o755)).unwrap();
+        let started = Instant::now();
+        let command = slow.to_str().unwrap();
+        assert_eq!(None, resolve(command, &file, Duration::from_millis(100)));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub const HEADER: [&str; 5] = ["path", "date", "make", "model", "strategy"];
+
+/// Metadata of a single scanned file
+#[derive(Debug, Default)]
+pub struct Row {
+    pub path: String,
+    pub date: String,
+    pub make: String,
+    pub model: String,
+    pub strategy: String,
+}
+
+impl Row {
+    fn fields(&self) -> [&str; 5] {
+        [
+            &self.path,
+            &self.date,
+            &self.make,
+            &self.model,
+            &self.strategy,
+        ]
+    }
+}
+
+// Write the rows with a header line, quoting fields as needed
+pub fn write(path: &Path, rows: &[Row]) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "{}", HEADER.join(","))?;
+    for row in rows {
+        let fields: Vec<String> = row.fields().iter().map(|field| escape(field)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Quote a field containing separators, quotes or line breaks, doubling the quotes
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("plain", escape("plain"));
+        assert_eq!("\"a,b\"", escape("a,b"));
+        assert_eq!("\"say \"\"cheese\"\"\"", escape("say \"cheese\""));
+    }
+
+    #[test]
+    fn csv_has_header_and_rows() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("exif.csv");
+        let rows = [Row {
+            path: String::from("/photos/trip, day 1.jpg"),
+            date: String::from("2022-12-17"),
+            make: String::from("OLYMPUS"),
+            model: String::from("E-M10"),
+            strategy: String::from("exif"),
+        }];
+        write(&path, &rows).unwrap();
+
+        assert_eq!(
+            "path,date,make,model,strategy\n\"/photos/trip, day 1.jpg\",2022-12-17,OLYMPUS,E-M10,exif\n",
+            fs::read_to_string(path).unwrap()
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use chrono::NaiveDateTime;
+use exif::experimental::Writer;
+use exif::{Field, In, Tag, Value};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Cursor};
+use std::path::Path;
+
+const SOI: [u8; 2] = [0xff, 0xd8];
+const APP0: u8 = 0xe0;
+const APP1: u8 = 0xe1;
+
+// Build an APP1 segment carrying the given exif fields
+pub fn exif_segment(fields: &[Field]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut writer = Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, false)?;
+    let tiff = tiff.into_inner();
+    let length = u16::try_from(tiff.len() + 8).map_err(|_| "exif data too large for a segment")?;
+
+    let mut segment = vec![0xff, APP1];
+    segment.extend_from_slice(&length.to_be_bytes());
+    segment.extend_from_slice(b"Exif\0\0");
+    segment.extend_from_slice(&tiff);
+    Ok(segment)
+}
+
+// Give a JPEG without any exif data a DateTimeOriginal, returning whether it
+// was written. The modification time and permissions of the file are kept.
+// The new content goes to a .tmp file next to it that replaces it once
+// complete, so the photo survives a full disk or a crash.
+pub fn write_date_time_original(jpeg: &Path, time: NaiveDateTime) -> io::Result<bool> {
+    let Some(written) = with_date_time_original(&fs::read(jpeg)?, time) else {
+        return Ok(false);
+    };
+    let metadata = fs::metadata(jpeg)?;
+    let mut name = jpeg.file_name().unwrap_or_default().to_owned();
+    name.push(".tmp");
+    let temp = jpeg.with_file_name(name);
+    let replaced = fs::write(&temp, written)
+        .and_then(|_| {
+            let file = File::options().write(true).open(&temp)?;
+            file.set_modified(metadata.modified()?)?;
+            file.set_permissions(metadata.permissions())
+        })
+        .and_then(|_| fs::rename(&temp, jpeg));
+    if replaced.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    replaced.map(|_| true)
+}
+
+// The content of a JPEG with a DateTimeOriginal added, None if it is no JPEG,
+// already has exif data or its JFIF header runs past the end of the file. The
+// segment goes right after the start of the image, or after a JFIF header.
+pub fn with_date_time_original(content: &[u8], time: NaiveDateTime) -> Option<Vec<u8>> {
+    if !content.starts_with(&SOI) || has_exif_segment(content) {
+        return None;
+    }
+    let value = time.format("%Y:%m:%d %H:%M:%S").to_string();
+    let segment = exif_segment(&[Field {
+        tag: Tag::DateTimeOriginal,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![value.into_bytes()]),
+    }])
+    .ok()?;
+    let at = match segment_at(content, SOI.len()) {
+        Some((APP0, length)) => SOI.len() + 2 + length,
+        _ => SOI.len(),
+    };
+    if at > content.len() {
+        return None;
+    }
+    let mut written = content[..at].to_vec();
+    written.extend_from_slice(&segment);
+    written.extend_from_slice(&content[at..]);
+    Some(written)
+}
+
+// The marker and length of the segment at an offset
+fn segment_at(content: &[u8], offset: usize) -> Option<(u8, usize)> {
+    match content.get(offset..offset + 4)? {
+        [0xff, marker, high, low] => Some((*marker, u16::from_be_bytes([*high, *low]) as usize)),
+        _ => None,
+    }
+}
+
+// Whether one of the segments before the image data is an exif APP1
+fn has_exif_segment(content: &[u8]) -> bool {
+    let mut offset = SOI.len();
+    while let Some((marker, length)) = segment_at(content, offset) {
+        // the start of scan is followed by the compressed image
+        if marker == 0xda || length < 2 {
+            return false;
+        }
+        if marker == APP1 && content[offset + 4..].starts_with(b"Exif\0\0") {
+            return true;
+        }
+        offset += 2 + length;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{ascii, jpeg_with_exif};
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    #[test]
+    fn written_date_reads_back() {
+        let tmpdir = TempDir::new().unwrap();
+        let jpeg = tmpdir.path().join("IMG_20210130_142501.jpg");
+        let jfif = [
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x06, b'J', b'F', b'I', b'F', 0xff, 0xd9,
+        ];
+        fs::write(&jpeg, jfif).unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::options()
+            .write(true)
+            .open(&jpeg)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let time =
+            NaiveDateTime::parse_from_str("2021-01-30 14:25:01", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        assert!(write_date_time_original(&jpeg, time).unwrap());
+        let content = fs::read(&jpeg).unwrap();
+        assert!(content.starts_with(&jfif[..10]));
+        let exif = crate::open_exif(jpeg.to_str().unwrap(), false).unwrap();
+        let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).unwrap();
+        assert_eq!("2021-01-30 14:25:01", field.display_value().to_string());
+        assert_eq!(modified, fs::metadata(&jpeg).unwrap().modified().unwrap());
+        assert_eq!(1, fs::read_dir(tmpdir.path()).unwrap().count());
+
+        assert!(!write_date_time_original(&jpeg, time).unwrap());
+        assert_eq!(content, fs::read(&jpeg).unwrap());
+    }
+
+    #[test]
+    fn existing_exif_is_left_alone() {
+        let tmpdir = TempDir::new().unwrap();
+        let jpeg = tmpdir.path().join("IMG_20210130_142501.jpg");
+        let original = jpeg_with_exif(&[ascii(Tag::Model, "X100")]);
+        fs::write(&jpeg, &original).unwrap();
+        let time =
+            NaiveDateTime::parse_from_str("2021-01-30 14:25:01", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        assert!(!write_date_time_original(&jpeg, time).unwrap());
+        assert_eq!(original, fs::read(&jpeg).unwrap());
+    }
+
+    #[test]
+    fn truncated_jfif_header_is_left_alone() {
+        let time =
+            NaiveDateTime::parse_from_str("2021-01-30 14:25:01", "%Y-%m-%d %H:%M:%S").unwrap();
+        // the APP0 length claims more bytes than the file has
+        let truncated = [0xff, 0xd8, 0xff, 0xe0, 0xff, 0xff, b'J', b'F', b'I', b'F'];
+        assert_eq!(None, with_date_time_original(&truncated, time));
+        let cut = [0xff, 0xd8, 0xff, 0xe0, 0x00, 0x06, b'J', b'F'];
+        assert_eq!(None, with_date_time_original(&cut, time));
+    }
+}
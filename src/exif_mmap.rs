@@ -0,0 +1,50 @@
+use memmap2::MmapOptions;
+use std::fs::File;
+use std::io::Cursor;
+
+/// How much of the start of a file is mapped. The exif data of JPEGs and of
+/// the TIFF based raw formats sits near the start.
+const HEADER_SIZE: u64 = 256 * 1024;
+
+// Parse the exif data from the memory mapped start of a file. None if the file
+// cannot be mapped or its exif data does not fit into the start, so the caller
+// falls back to reading the file.
+pub fn read_header(file: &File) -> Option<exif::Exif> {
+    let len = file.metadata().ok()?.len().min(HEADER_SIZE);
+    if len == 0 {
+        return None;
+    }
+    // SAFETY: the map is only read while parsing. A file truncated meanwhile
+    // by another process faults, which is why mapping is opt-in.
+    let map = unsafe { MmapOptions::new().len(len as usize).map(file) }.ok()?;
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(&map[..]))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{ascii, jpeg_with_exif};
+    use exif::{In, Tag};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn exif_is_read_from_the_mapped_header() {
+        let tmpdir = TempDir::new().unwrap();
+        let image = tmpdir.path().join("exif.jpg");
+        fs::write(
+            &image,
+            jpeg_with_exif(&[ascii(Tag::DateTimeOriginal, "2019:07:04 12:00:00")]),
+        )
+        .unwrap();
+        let exif = read_header(&File::open(&image).unwrap()).unwrap();
+        let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).unwrap();
+        assert_eq!("2019-07-04 12:00:00", field.display_value().to_string());
+
+        let empty = tmpdir.path().join("empty.jpg");
+        File::create(&empty).unwrap();
+        assert!(read_header(&File::open(&empty).unwrap()).is_none());
+    }
+}
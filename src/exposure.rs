@@ -0,0 +1,88 @@
+use exif::{Exif, In, Tag};
+
+/// Limits on the exposure settings of photos, set with --min-iso,
+/// --max-aperture and --min-focal-length
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExposureFilter {
+    pub min_iso: Option<u32>,
+    pub max_aperture: Option<f64>,
+    pub min_focal_length: Option<f64>,
+}
+
+/// The exposure settings recorded in the EXIF data of a photo
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Exposure {
+    pub iso: Option<u32>,
+    pub f_number: Option<f64>,
+    pub focal_length: Option<f64>,
+}
+
+pub fn read_exposure(exif: &Exif) -> Exposure {
+    let field = |tag| exif.get_field(tag, In::PRIMARY).map(|field| &field.value);
+    let rational = |tag| match field(tag) {
+        Some(exif::Value::Rational(values)) => values.first().map(|value| value.to_f64()),
+        _ => None,
+    };
+    Exposure {
+        iso: field(Tag::PhotographicSensitivity).and_then(|value| value.get_uint(0)),
+        f_number: rational(Tag::FNumber),
+        focal_length: rational(Tag::FocalLength),
+    }
+}
+
+impl ExposureFilter {
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+
+    // Whether an exposure is within every set limit. A photo without the
+    // setting a limit is about is outside of it.
+    pub fn accepts(&self, exposure: &Exposure) -> bool {
+        let min_iso = self.min_iso.map(f64::from);
+        let iso = exposure.iso.map(f64::from);
+        within(min_iso, iso, |iso, min| iso >= min)
+            && within(self.max_aperture, exposure.f_number, |f, max| f <= max)
+            && within(self.min_focal_length, exposure.focal_length, |mm, min| {
+                mm >= min
+            })
+    }
+}
+
+fn within(limit: Option<f64>, value: Option<f64>, fits: fn(f64, f64) -> bool) -> bool {
+    limit.is_none_or(|limit| value.is_some_and(|value| fits(value, limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_are_inclusive() {
+        let filter = ExposureFilter {
+            min_iso: Some(1600),
+            max_aperture: Some(2.8),
+            ..Default::default()
+        };
+        let exposure = Exposure {
+            iso: Some(1600),
+            f_number: Some(2.8),
+            focal_length: None,
+        };
+        assert!(filter.is_set());
+        assert!(filter.accepts(&exposure));
+        assert!(!filter.accepts(&Exposure {
+            iso: Some(800),
+            ..exposure
+        }));
+        assert!(!filter.accepts(&Exposure {
+            f_number: Some(4.0),
+            ..exposure
+        }));
+        assert!(!filter.accepts(&Exposure {
+            f_number: None,
+            ..exposure
+        }));
+        assert!(!ExposureFilter::default().is_set());
+        assert!(ExposureFilter::default().accepts(&Exposure::default()));
+    }
+}
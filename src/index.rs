@@ -0,0 +1,139 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Hidden marker written into each target folder with `--write-index`
+pub const INDEX_FILE: &str = ".mediasort-index.json";
+
+/// Files mediasort placed into a single target folder, keyed by filename
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FolderIndex {
+    pub files: BTreeMap<String, IndexEntry>,
+    /// Other sources with the same content as a file, which were skipped
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub source: String,
+    pub copied_at: String,
+}
+
+impl FolderIndex {
+    // Load the index of a folder, starting fresh if there is none or it is unreadable
+    pub fn load(folder: &Path) -> Self {
+        let path = folder.join(INDEX_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable index {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, folder: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(folder.join(INDEX_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// Merge the copied (source, target) pairs into the index of each target folder
+pub fn write_indexes(copied: &[(String, PathBuf)]) -> Result<(), Box<dyn Error>> {
+    let copied_at = Local::now().to_rfc3339();
+    update_indexes(copied, |index, source, name| {
+        index.files.insert(
+            name,
+            IndexEntry {
+                source: source.to_string(),
+                copied_at: copied_at.clone(),
+            },
+        );
+    })
+}
+
+// Note the skipped (source, target) duplicates as aliases of their target in
+// the index of its folder, each source only once
+pub fn record_aliases(aliases: &[(String, PathBuf)]) -> Result<(), Box<dyn Error>> {
+    update_indexes(aliases, |index, source, name| {
+        let sources = index.aliases.entry(name).or_default();
+        if !sources.iter().any(|known| known == source) {
+            sources.push(source.to_string());
+        }
+    })
+}
+
+// Group (source, target) pairs by the folder of the target and apply each
+// source and target filename to the index of that folder
+fn update_indexes(
+    pairs: &[(String, PathBuf)],
+    mut update: impl FnMut(&mut FolderIndex, &str, String),
+) -> Result<(), Box<dyn Error>> {
+    let mut folders: HashMap<&Path, Vec<(&str, String)>> = HashMap::new();
+    for (source, target) in pairs {
+        if let (Some(folder), Some(name)) = (target.parent(), target.file_name()) {
+            folders
+                .entry(folder)
+                .or_default()
+                .push((source, name.to_string_lossy().into_owned()));
+        }
+    }
+    for (folder, files) in folders {
+        let mut index = FolderIndex::load(folder);
+        for (source, name) in files {
+            update(&mut index, source, name);
+        }
+        index.save(folder)?;
+        debug!("Updated index in {}", folder.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn index_is_merged_across_runs() {
+        let tmpdir = TempDir::new().unwrap();
+        let folder = tmpdir.path().join("2021/01/30");
+        fs::create_dir_all(&folder).unwrap();
+
+        write_indexes(&[(String::from("/src/a.jpg"), folder.join("a.jpg"))]).unwrap();
+        write_indexes(&[(String::from("/src/b.jpg"), folder.join("b.jpg"))]).unwrap();
+
+        let index = FolderIndex::load(&folder);
+        assert_eq!(
+            vec!["a.jpg", "b.jpg"],
+            index.files.keys().collect::<Vec<_>>()
+        );
+        assert_eq!("/src/b.jpg", index.files["b.jpg"].source);
+    }
+
+    #[test]
+    fn aliases_are_recorded_once() {
+        let tmpdir = TempDir::new().unwrap();
+        let folder = tmpdir.path().join("2021/01/30");
+        fs::create_dir_all(&folder).unwrap();
+
+        write_indexes(&[(String::from("/src/a.jpg"), folder.join("a.jpg"))]).unwrap();
+        let aliases = [
+            (String::from("/backup/a.jpg"), folder.join("a.jpg")),
+            (String::from("/phone/a.jpg"), folder.join("a.jpg")),
+        ];
+        record_aliases(&aliases).unwrap();
+        record_aliases(&aliases[..1]).unwrap();
+
+        let index = FolderIndex::load(&folder);
+        assert_eq!("/src/a.jpg", index.files["a.jpg"].source);
+        assert_eq!(
+            vec!["/backup/a.jpg", "/phone/a.jpg"],
+            index.aliases["a.jpg"]
+        );
+    }
+}
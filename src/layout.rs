@@ -0,0 +1,197 @@
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+
+// How many year folders of an existing target are looked at to detect its layout
+const LAYOUT_SAMPLE_SIZE: usize = 20;
+
+/// How deep the date folders of the target go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Granularity {
+    /// YYYY/
+    Year,
+    /// YYYY/MM/
+    Month,
+    /// YYYY/MM/DD/
+    #[default]
+    Day,
+}
+
+impl Granularity {
+    fn depth(self) -> usize {
+        match self {
+            Granularity::Year => 1,
+            Granularity::Month => 2,
+            Granularity::Day => 3,
+        }
+    }
+
+    fn from_depth(depth: usize) -> Option<Self> {
+        match depth {
+            1 => Some(Granularity::Year),
+            2 => Some(Granularity::Month),
+            3 => Some(Granularity::Day),
+            _ => None,
+        }
+    }
+}
+
+/// Named folder layouts expected by other tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// YYYY/YYYY-MM-DD/, one folder per day as Plex likes it
+    Plex,
+    /// YYYY/
+    SimpleYear,
+}
+
+impl Layout {
+    pub fn granularity(self) -> Granularity {
+        match self {
+            Layout::Plex => Granularity::Day,
+            Layout::SimpleYear => Granularity::Year,
+        }
+    }
+
+    // Rename the date folders of a relative target for the layout
+    pub fn format(self, target: &str) -> String {
+        match self {
+            Layout::SimpleYear => target.to_string(),
+            Layout::Plex => {
+                let parts: Vec<&str> = target.splitn(4, '/').collect();
+                match parts.as_slice() {
+                    [y, m, d, rest] if [y, m, d].iter().all(|part| is_number(part)) => {
+                        format!("{}/{}-{}-{}/{}", y, y, m, d, rest)
+                    }
+                    _ => target.to_string(),
+                }
+            }
+        }
+    }
+}
+
+// Drop the date folders of a relative target beyond the granularity
+pub fn apply_granularity(target: &str, granularity: Granularity) -> String {
+    let parts: Vec<&str> = target.split('/').collect();
+    let dates = parts
+        .iter()
+        .take(parts.len().saturating_sub(1))
+        .take_while(|part| is_number(part))
+        .count();
+    parts
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i >= dates || *i < granularity.depth())
+        .map(|(_, part)| *part)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Detect the granularity an existing target already uses by sampling its year
+// folders. None if the target has no date folders or they disagree.
+pub fn detect_granularity(target: &Path) -> Option<Granularity> {
+    let mut depths = date_folders(target, 4)
+        .take(LAYOUT_SAMPLE_SIZE)
+        .map(|year| 1 + date_depth(&year));
+    let first = depths.next()?;
+    if depths.all(|depth| depth == first) {
+        Granularity::from_depth(first)
+    } else {
+        None
+    }
+}
+
+// How many levels of two digit month and day folders are below a folder
+fn date_depth(folder: &Path) -> usize {
+    let mut depth = 0;
+    let mut current = folder.to_path_buf();
+    while depth < 2 {
+        match date_folders(&current, 2).next() {
+            Some(next) => {
+                current = next;
+                depth += 1;
+            }
+            None => break,
+        }
+    }
+    depth
+}
+
+// The subfolders with a numeric name of the given length, sorted by name
+fn date_folders(folder: &Path, len: usize) -> impl Iterator<Item = std::path::PathBuf> {
+    let mut folders: Vec<_> = fs::read_dir(folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.len() == len && is_number(name))
+        })
+        .collect();
+    folders.sort();
+    folders.into_iter()
+}
+
+fn is_number(part: &str) -> bool {
+    !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_apply_granularity() {
+        let target = "2021/01/30/serial/IMG_1.jpg";
+        assert_eq!(
+            "2021/serial/IMG_1.jpg",
+            apply_granularity(target, Granularity::Year)
+        );
+        assert_eq!(
+            "2021/01/serial/IMG_1.jpg",
+            apply_granularity(target, Granularity::Month)
+        );
+        assert_eq!(target, apply_granularity(target, Granularity::Day));
+        assert_eq!(
+            "2005/scan.jpg",
+            apply_granularity("2005/scan.jpg", Granularity::Day)
+        );
+        assert_eq!(
+            "2005/1234.jpg",
+            apply_granularity("2005/07/1234.jpg", Granularity::Year)
+        );
+    }
+
+    #[test]
+    fn layout_presets() {
+        let target = "2021/01/30/IMG_1.jpg";
+        assert_eq!("2021/2021-01-30/IMG_1.jpg", Layout::Plex.format(target));
+        assert_eq!("2005/07/scan.jpg", Layout::Plex.format("2005/07/scan.jpg"));
+        assert_eq!(Granularity::Year, Layout::SimpleYear.granularity());
+    }
+
+    #[test]
+    fn detect_existing_layout() {
+        let tmpdir = TempDir::new().unwrap();
+        assert_eq!(None, detect_granularity(tmpdir.path()));
+
+        fs::create_dir_all(tmpdir.path().join("2020/05")).unwrap();
+        fs::create_dir_all(tmpdir.path().join("2021/12")).unwrap();
+        fs::create_dir_all(tmpdir.path().join("Albums/Holiday")).unwrap();
+        assert_eq!(Some(Granularity::Month), detect_granularity(tmpdir.path()));
+
+        fs::create_dir_all(tmpdir.path().join("2022/01/15")).unwrap();
+        assert_eq!(None, detect_granularity(tmpdir.path()));
+    }
+
+    #[test]
+    fn detect_day_layout() {
+        let tmpdir = TempDir::new().unwrap();
+        fs::create_dir_all(tmpdir.path().join("2021/01/30")).unwrap();
+        assert_eq!(Some(Granularity::Day), detect_granularity(tmpdir.path()));
+    }
+}
@@ -1,264 +1,5722 @@
 #[macro_use]
 extern crate log;
 
-use clap::Parser;
+mod archive;
+mod avchd;
+mod camera;
+mod cas;
+mod catalog;
+mod checksum;
+mod chunk;
+mod conflict;
+mod contact_sheet;
+mod date_command;
+mod exif_csv;
+mod exif_date;
+mod exif_mmap;
+mod exposure;
+mod index;
+mod layout;
+mod link;
+mod motion;
+mod pdf;
+mod priority;
+mod progress;
+mod reference;
+mod rename;
+mod report;
+mod scan_cache;
+mod selftest;
+mod suggest;
+#[cfg(test)]
+mod testutil;
+mod unknown_ext;
+mod verify;
+mod video;
+mod xmp;
+
+pub use checksum::ChecksumAlgo;
+use chrono::{DateTime, Datelike, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use conflict::CollisionLog;
+pub use conflict::ConflictPolicy;
 use exif::{In, Tag};
+use exposure::ExposureFilter;
+pub use layout::{Granularity, Layout};
 use lazy_static::lazy_static;
+use progress::Progress;
+use rayon::prelude::*;
 use regex::Regex;
-use std::collections::HashMap;
+pub use report::CopySummary;
+use report::{Report, Status};
+use scan_cache::{CachedDate, ScanCache};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::{env, error::Error, fs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, error::Error, fmt, fs, io, thread};
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 pub struct Args {
-    /// the name of the directory to parse
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// the name of the directory to parse, or a zip/tar archive to import from
+    #[arg(short, long, required_unless_present = "from_camera")]
+    folder: Option<String>,
+
+    /// Download from a connected camera or phone with gphoto2 and sort that instead
+    #[arg(long, conflicts_with = "folder")]
+    from_camera: bool,
+
+    /// the directory to sort into, defaults to $MEDIASORT_TARGET or ~/Pictures
     #[arg(short, long)]
-    folder: String,
+    target: Option<PathBuf>,
 
     /// Should the directory be parsed recursively
-    #[arg(short, long, default_value_t = true)]
+    #[arg(short, long, default_value_t = true, action = ArgAction::Set)]
     recursive: bool,
+
+    /// Skip subdirectories that contain no media files at any depth
+    #[arg(long)]
+    skip_empty_dirs: bool,
+
+    /// Only show what would be copied, without touching any files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Write a JSON report of all processed files to this path
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Sort videos into this directory instead of the target
+    #[arg(long)]
+    video_target: Option<PathBuf>,
+
+    /// Sort screenshots into this directory instead of the target
+    #[arg(long)]
+    screenshots_target: Option<PathBuf>,
+
+    /// Sort a media category (image, raw, video) or extension into its own root,
+    /// e.g. raw=/mnt/raw, can be given multiple times
+    #[arg(long, value_parser = parse_category_target)]
+    category_target: Vec<(String, PathBuf)>,
+
+    /// Sort files dated before this date (YYYY-MM-DD), or older than this many
+    /// months (e.g. 12m), into --archive-target instead
+    #[arg(long, value_name = "DATE|Nm", value_parser = parse_archive_before, requires = "archive_target")]
+    archive_before: Option<NaiveDate>,
+
+    /// Root for the files older than --archive-before
+    #[arg(long, requires = "archive_before")]
+    archive_target: Option<PathBuf>,
+
+    /// Only scan and write the path, date, camera and strategy of each file to a CSV
+    #[arg(long)]
+    exif_csv: Option<PathBuf>,
+
+    /// Only scan a sample of N files, report how many each strategy dates and suggest flags
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "100")]
+    suggest_config: Option<usize>,
+
+    /// Only scan and list the files whose planned target differs from where this
+    /// existing tree keeps the same content
+    #[arg(long, value_name = "DIR")]
+    compare_with_reference: Option<PathBuf>,
+
+    /// Only scan and count the extensions of the files that are not sorted as media
+    #[arg(long)]
+    report_unknown_ext: bool,
+
+    /// Keep a hidden index of the placed files in each target folder
+    #[arg(long)]
+    write_index: bool,
+
+    /// Record a skipped source whose target has the same content as an alias
+    /// of that target in the index
+    #[arg(long, requires = "write_index")]
+    dedupe_keep_original_location: bool,
+
+    /// How deep the date folders go
+    #[arg(long, value_enum, default_value_t = Granularity::Day)]
+    granularity: Granularity,
+
+    /// Sort into the folder layout of another tool, instead of choosing a granularity
+    #[arg(long, value_enum, conflicts_with = "granularity")]
+    layout: Option<Layout>,
+
+    /// Adopt the granularity of the date folders already in the target, if any
+    #[arg(long)]
+    merge_into_existing_structure: bool,
+
+    /// Add a folder named after the weekday below the date folders, like 2021/01/30/Saturday/
+    #[arg(long)]
+    with_weekday: bool,
+
+    /// Names of the weekday folders from Monday to Sunday, English by default
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "NAMES",
+        requires = "with_weekday"
+    )]
+    weekday_names: Vec<String>,
+
+    /// Prefix copied names with a sequence number, counting through each day, target folder or event
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = ["in_place_rename", "cas", "resume_cursor", "max_files_in_memory"]
+    )]
+    sequence_scope: Option<SequenceScope>,
+
+    /// Give copies the owner, group and mode of their source, Unix only
+    #[arg(long)]
+    copy_attributes: bool,
+
+    /// Copy the extended attributes (Finder tags etc.) along with the file, Unix only
+    #[arg(long)]
+    preserve_xattr: bool,
+
+    /// Of several source files with the same content copy only one
+    #[arg(long)]
+    dedup_identical: bool,
+
+    /// Move instead of copy: the source is only deleted once its copy matches by checksum
+    #[arg(long = "move")]
+    move_files: bool,
+
+    /// Hash used to verify moved files
+    #[arg(long, value_enum, default_value_t = ChecksumAlgo::Sha256)]
+    checksum_algo: ChecksumAlgo,
+
+    /// Copy the XMP sidecars of every copied file along with it
+    #[arg(long)]
+    sidecars: bool,
+
+    /// Also update the sidecars of files skipped because they already exist
+    #[arg(long)]
+    sync_sidecars: bool,
+
+    /// Abort before copying if the target does not have room for all files
+    #[arg(long)]
+    check_free_space: bool,
+
+    /// Write a _contactsheet.jpg with thumbnails of the images copied into each folder
+    #[arg(long)]
+    contact_sheet: bool,
+
+    /// Rename files to YYYYMMDD_HHMMSS where they are instead of sorting them into the target
+    #[arg(long, conflicts_with_all = ["move_files", "symlink", "cas"])]
+    in_place_rename: bool,
+
+    /// Create symlinks to the originals instead of copying them, leaving the originals untouched
+    #[arg(long, conflicts_with = "move_files", group = "links")]
+    symlink: bool,
+
+    /// Store each distinct content once in this hash-sharded folder and symlink the dated
+    /// targets to it
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with = "move_files",
+        group = "links"
+    )]
+    cas: Option<PathBuf>,
+
+    /// Make the symlinks relative to their folder instead of absolute
+    #[arg(long, requires = "links")]
+    relative_links: bool,
+
+    /// Scan and copy one folder at a time, resuming after the folder recorded in this file
+    #[arg(long, value_name = "FILE")]
+    resume_cursor: Option<PathBuf>,
+
+    /// Copy the files found so far whenever this many are scanned, keeping memory flat
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_files_in_memory: Option<u64>,
+
+    /// Log and skip files whose metadata parsing panics instead of aborting
+    #[arg(long)]
+    catch_panics: bool,
+
+    /// Sort into a subfolder per camera body serial number below the date
+    #[arg(long)]
+    by_serial: bool,
+
+    /// Sort into a subfolder per lens model below the date
+    #[arg(long)]
+    by_lens: bool,
+
+    /// Sort photos into portrait, landscape or square subfolders below the date
+    #[arg(long)]
+    by_aspect: bool,
+
+    /// Sort videos by their aspect too, with --by-aspect
+    #[arg(long, requires = "by_aspect")]
+    aspect_videos: bool,
+
+    /// Shift the EXIF times of a camera model or body serial whose clock is off,
+    /// e.g. E-M10MarkII=+01:30, can be given multiple times
+    #[arg(long, value_name = "CAMERA=[+-]HH:MM[:SS]", value_parser = parse_camera_offset)]
+    camera_offset: Vec<(String, TimeDelta)>,
+
+    /// Remember the date of every file and reuse it while the file is unchanged
+    #[arg(long, value_name = "PATH")]
+    scan_cache: Option<PathBuf>,
+
+    /// Sort iCloud/OneDrive placeholders of files that are not downloaded, fetching each one
+    #[arg(long)]
+    download_placeholders: bool,
+
+    /// Append the totals of every run as a timestamped JSON line to this file
+    #[arg(long, value_name = "FILE")]
+    run_log: Option<PathBuf>,
+
+    /// Also record every copied file with its date, hash and camera in this SQLite database
+    /// (needs the catalog feature)
+    #[arg(long, value_name = "FILE")]
+    catalog: Option<PathBuf>,
+
+    /// Show the copied files and bytes with the throughput and an estimate of the remaining time
+    #[arg(long)]
+    progress_eta: bool,
+
+    /// Hash every copy again once all are done and compare it with its source
+    #[arg(long)]
+    verify_all: bool,
+
+    /// Lower the CPU and I/O priority, so a long run does not slow down other programs
+    #[arg(long)]
+    low_priority: bool,
+
+    /// Number of threads reading metadata while scanning, defaults to the number of CPUs
+    #[arg(long, value_name = "N")]
+    scan_threads: Option<usize>,
+
+    /// Parse EXIF data from the memory mapped start of each file instead of reading it
+    #[arg(long)]
+    mmap_exif: bool,
+
+    /// Copy into a .tmp sibling and rename it into place, so no half-written file is visible
+    #[arg(long)]
+    atomic: bool,
+
+    /// Write the temporary files of --atomic into this folder, on the target's filesystem
+    #[arg(long, value_name = "DIR", requires = "atomic")]
+    temp_dir: Option<PathBuf>,
+
+    /// Wait for space to be freed when the target fills up instead of stopping the run
+    #[arg(long)]
+    wait_on_disk_full: bool,
+
+    /// Sort into a subfolder per lowercase file extension below the date, e.g. jpg/
+    #[arg(long)]
+    split_by_ext: bool,
+
+    /// Normalize target names to Unicode NFC, so names written in NFC and NFD become one
+    #[arg(long)]
+    normalize_unicode: bool,
+
+    /// Write the date of the filename as EXIF DateTimeOriginal into copied JPEGs without exif
+    #[arg(long)]
+    write_exif_date: bool,
+
+    /// Sort photos with named face regions into people/<name>/ before the date
+    #[arg(long)]
+    by_person: bool,
+
+    /// Which person a photo of several people goes to
+    #[arg(long, value_enum, default_value_t = PersonRule::First, requires = "by_person")]
+    person_rule: PersonRule,
+
+    /// Skip images smaller than this, e.g. 640x480 to leave out thumbnails and icons
+    #[arg(long, value_name = "WxH", value_parser = parse_resolution)]
+    min_resolution: Option<(u32, u32)>,
+
+    /// Only sort photos taken with at least this ISO
+    #[arg(long, value_name = "ISO")]
+    min_iso: Option<u32>,
+
+    /// Only sort photos taken with at most this f-number, e.g. 2.8 for f/2.8 and wider
+    #[arg(long, value_name = "F_NUMBER")]
+    max_aperture: Option<f64>,
+
+    /// Only sort photos taken with at least this focal length in millimeters
+    #[arg(long, value_name = "MM")]
+    min_focal_length: Option<f64>,
+
+    /// Skip files whose content hash (see --checksum-algo) is listed in this file, one per line
+    #[arg(long, value_name = "FILE")]
+    skip_hashes: Option<PathBuf>,
+
+    /// Skip files modified within the last N seconds, e.g. while they are still being copied
+    #[arg(long, value_name = "SECONDS")]
+    min_age: Option<u64>,
+
+    /// Retry files locked by another process once more at the end of the run
+    #[arg(long)]
+    retry_locked: bool,
+
+    /// Compare the size of each copy with its source and retry truncated copies
+    #[arg(long)]
+    verify_size: bool,
+
+    /// Skip hardlinked files already found under another path (Unix only)
+    #[arg(long)]
+    dedup_inodes: bool,
+
+    /// Print the run totals as a single JSON object instead of the summary line
+    #[arg(long)]
+    summary_json: bool,
+
+    /// Keep Live Photo videos with their still and note embedded motion photos
+    #[arg(long)]
+    motion_photos: bool,
+
+    /// Copy files whose metadata can not be parsed as-is into a corrupt/ folder
+    #[arg(long)]
+    quarantine_corrupt: bool,
+
+    /// Fail before copying when media files of a format this build cannot sort are found
+    #[arg(long, alias = "fail-fast-on-unsupported")]
+    strict_formats: bool,
+
+    /// What to do when a target file already exists
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Rename)]
+    on_conflict: ConflictPolicy,
+
+    /// Append every rename caused by a collision to this file
+    #[arg(long)]
+    collision_log: Option<PathBuf>,
+
+    /// The order in which the date strategies are tried
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = Strategy::DEFAULT_ORDER
+    )]
+    strategy_order: Vec<Strategy>,
+
+    /// Strategy order for images and raw files only, e.g. exif,mtime, instead of --strategy-order
+    #[arg(long, value_enum, value_delimiter = ',')]
+    image_date: Vec<Strategy>,
+
+    /// Strategy order for videos only, e.g. mtime, instead of --strategy-order
+    #[arg(long, value_enum, value_delimiter = ',')]
+    video_date: Vec<Strategy>,
+
+    /// Use the oldest date any strategy finds instead of the first one, edits tend to be later
+    #[arg(long)]
+    oldest_date: bool,
+
+    /// Captures before this time of day (HH:MM) count as the previous day, e.g. 04:00
+    #[arg(long, value_name = "HH:MM", value_parser = parse_day_cutoff)]
+    day_cutoff: Option<NaiveTime>,
+
+    /// Only trust the dates in the filenames of these extensions, e.g. mp4,mov
+    #[arg(long, value_delimiter = ',', value_name = "EXT")]
+    filename_date_ext: Vec<String>,
+
+    /// Run this command with the path of a file the built-in strategies find no date
+    /// for, before falling back to mtime, and use the ISO date it prints
+    #[arg(long, value_name = "CMD")]
+    date_command: Option<String>,
+
+    /// Seconds after which a --date-command is stopped and its file skipped
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    date_command_timeout: u64,
 }
 
-#[derive(Debug)]
-pub struct MediaConfig {
-    pub source: String,
-    pub target: PathBuf,
-    files: HashMap<String, String>,
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run every date strategy against built-in samples, without touching any files
+    Selftest,
 }
 
-impl MediaConfig {
-    pub fn new(source: String, target: PathBuf) -> Self {
-        Self {
-            source,
-            target,
-            files: HashMap::new(),
-        }
+/// Which files share a counter of --sequence-scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SequenceScope {
+    /// The files taken on the same day
+    Day,
+    /// The files sorted into the same folder, across days
+    Folder,
+    /// The files of the same source folder, like the album of an event, across days
+    Event,
+}
+
+/// Which people a photo of several people is sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PersonRule {
+    /// The first named face region
+    #[default]
+    First,
+    /// The first name in alphabetical order
+    Alphabetical,
+    /// All names together, like people/Anna+Ben/
+    All,
+}
+
+/// The ways to determine the date of a media file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Smartphone filenames like IMG_20230115_102911.jpg
+    Filename,
+    /// The EXIF DateTimeOriginal tag, else DateTimeDigitized or DateTime
+    Exif,
+    /// The QuickTime/MP4 metadata of videos
+    Video,
+    /// The creation date in the metadata of PDF scans, needs the pdf feature
+    Pdf,
+    /// Bare sequence numbers in a folder named after a full date, like Samsung bursts
+    Burst,
+    /// A date in the name of the parent directory, like "2005-07 Summer Trip"
+    Directory,
+    /// The modification time of the file
+    Mtime,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_possible_value().unwrap().get_name())
     }
+}
 
-    pub fn copy_media_files(&mut self) -> Result<(), Box<dyn Error>> {
-        self.find_all_media_files(None, true)?;
-        info!("Found {} files", self.files.len());
-        let mut copied_files = 0;
-        for (source, target) in self.files.iter() {
-            match copy_file(source, target) {
-                Ok(true) => copied_files += 1,
-                Ok(false) => (),
-                Err(e) => error!("Error copying file: {}", e),
-            }
+impl Strategy {
+    pub const DEFAULT_ORDER: [Strategy; 7] = [
+        Strategy::Filename,
+        Strategy::Exif,
+        Strategy::Video,
+        Strategy::Pdf,
+        Strategy::Burst,
+        Strategy::Directory,
+        Strategy::Mtime,
+    ];
+
+    fn target_path(self, filename: &str, options: &Options) -> Option<String> {
+        match self {
+            Strategy::Filename => smartphone_file(filename),
+            Strategy::Exif => read_exif(filename, options.mmap_exif),
+            Strategy::Video => read_video_date(filename),
+            Strategy::Pdf => read_pdf_date(filename),
+            Strategy::Burst => read_burst_date(filename),
+            Strategy::Directory => read_directory_date(filename),
+            Strategy::Mtime => read_modified_date(filename),
         }
-        info!("Copied {}/{} files", copied_files, self.files.len());
-        Ok(())
     }
 
-    fn find_all_media_files(
-        &mut self,
-        path: Option<&str>,
-        recursive: bool,
-    ) -> Result<(), Box<dyn Error>> {
-        let path = path.unwrap_or(&self.source);
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() && recursive {
-                self.find_all_media_files(Some(path.to_str().unwrap()), true)?;
-            } else if path.is_file() && is_media_file(&path) {
-                let sourcepath = &path.to_str().unwrap();
-                if let Some(targetpath) = smartphone_file(sourcepath) {
-                    self.files
-                        .insert(sourcepath.to_string(), targetpath.to_owned());
-                } else if let Some(targetpath) = read_jpg_exif(sourcepath) {
-                    self.files
-                        .insert(sourcepath.to_string(), targetpath.to_owned());
-                }
-            }
+    fn date_source(self) -> DateSource {
+        match self {
+            Strategy::Filename => DateSource::SmartphoneName,
+            Strategy::Exif => DateSource::Exif,
+            Strategy::Video => DateSource::VideoMetadata,
+            Strategy::Pdf => DateSource::PdfMetadata,
+            Strategy::Burst => DateSource::BurstFolder,
+            Strategy::Directory => DateSource::DirectoryName,
+            Strategy::Mtime => DateSource::Mtime,
         }
-        Ok(())
     }
 }
 
-fn is_media_file(path: &Path) -> bool {
-    let ext = path.extension();
+/// Where the date of a media file was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateSource {
+    SmartphoneName,
+    Exif,
+    VideoMetadata,
+    PdfMetadata,
+    BurstFolder,
+    DateCommand,
+    DirectoryName,
+    Mtime,
+}
 
-    match ext {
-        None => false,
-        Some(file_ext) => matches!(
-            String::from(file_ext.to_str().unwrap())
-                .to_lowercase()
-                .as_str(),
-            "jpg" | "jpeg" | "mp4" | "png"
-        ),
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DateSource::SmartphoneName => "smartphone_name",
+            DateSource::Exif => "exif",
+            DateSource::VideoMetadata => "video_metadata",
+            DateSource::PdfMetadata => "pdf_metadata",
+            DateSource::BurstFolder => "burst_folder",
+            DateSource::DateCommand => "date_command",
+            DateSource::DirectoryName => "directory_name",
+            DateSource::Mtime => "mtime",
+        })
     }
 }
 
-pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
-    let home = env::var("HOME")?;
-    let target = Path::new(&home).join("Pictures");
-    MediaConfig::new(args.folder, target).copy_media_files()?;
-    Ok(())
+/// Where a media file goes, relative to its target root, and how its date was found.
+/// Files without a date source, like quarantined ones, are not sorted by date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaEntry {
+    pub target: String,
+    pub date_source: Option<DateSource>,
+    /// The date folders the file was dated by, like 2021/01/30, before the
+    /// folder options change them
+    pub date: Option<String>,
 }
 
-// Copy file from one directory to another
-fn copy_file(from: &str, to: &str) -> Result<bool, Box<dyn Error>> {
-    let abs_path = Path::new(&to);
-    let parent = abs_path.parent().unwrap();
-    create_dir(parent.to_str().unwrap())?;
-    if abs_path.exists() {
-        warn!("Skipping File {}, already exists", to);
-        return Ok(false);
+impl MediaEntry {
+    // The entry of a file dated by a date path like 2021/01/30/name.jpg
+    fn dated(target: String, date_source: DateSource) -> Self {
+        Self {
+            date: Some(date_folders(&target)),
+            target,
+            date_source: Some(date_source),
+        }
     }
-    info!("Copy file {} to {}", from, abs_path.to_str().unwrap());
-    fs::copy(from, to)?;
-    Ok(true)
-}
 
-// Create directory, if it does not exist
-fn create_dir(path: &str) -> Result<(), Box<dyn Error>> {
-    fs::create_dir_all(path)?;
-    Ok(())
+    // Human readable origin of the target for log messages
+    fn origin(&self) -> String {
+        match self.date_source {
+            Some(source) => format!("date from {}", source),
+            None => String::from("undated"),
+        }
+    }
 }
 
-// Read date from smartphone image or video filename
-fn smartphone_file(filename: &str) -> Option<String> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(
-            r"(?x)
-  (?:IMG|VID)_
-  (?P<y>\d{4}) # the year
-  (?P<m>\d{2}) # the month
-  (?P<d>\d{2}) # the day
-  _(\d{6}).(?:jpg|mp4)
-"
-        )
-        .unwrap();
-    };
-
-    RE.captures(filename)
-        .map(|cap| format!("{}/{}/{}/{}", &cap["y"], &cap["m"], &cap["d"], &cap[0]))
+/// Counters and records kept while copying. The records are only kept for
+/// the options that need them, so a run without them uses no memory per file.
+struct Tally {
+    summary: CopySummary,
+    report: Option<Report>,
+    copied: Option<Vec<(String, PathBuf)>>,
+    cataloged: Vec<catalog::Entry>,
+    hashed: Vec<verify::Recorded>,
+    aliases: Vec<(String, PathBuf)>,
+    staged: Option<(String, String)>,
 }
 
-fn read_jpg_exif(filename: &str) -> Option<String> {
-    // filename needs to end with .jpg or .png
-    if !filename.to_lowercase().ends_with(".jpg") && !filename.to_lowercase().ends_with(".png") {
-        return None;
+impl Tally {
+    fn new(options: &Options, staged: Option<(String, String)>) -> Self {
+        Self {
+            summary: CopySummary::default(),
+            report: options
+                .report
+                .as_ref()
+                .map(|_| Report::new(options.dry_run)),
+            copied: (options.write_index || options.contact_sheet).then(Vec::new),
+            cataloged: Vec::new(),
+            hashed: Vec::new(),
+            aliases: Vec::new(),
+            staged,
+        }
     }
-    lazy_static! {
-        static ref RE: Regex =
-            Regex::new(r"(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})\s+(?:\d|:){8}").unwrap();
-    };
-    let file = File::open(filename).unwrap_or_else(|_| panic!("Could not open file {}", filename));
-    let mut bufreader = std::io::BufReader::new(&file);
-    let exifreader = exif::Reader::new();
-    let exif = exifreader.read_from_container(&mut bufreader).unwrap();
-    let datetime = match exif.get_field(Tag::DateTimeOriginal, In::PRIMARY) {
-        Some(field) => RE
-            .captures(field.display_value().to_string().as_str())
-            .map(|cap| {
-                format!(
-                    "{}/{}/{}/{}",
-                    &cap["y"],
-                    &cap["m"],
-                    &cap["d"],
-                    Path::new(filename)
-                        .file_name()
-                        .expect("no filename")
-                        .to_str()
-                        .unwrap()
-                )
-            }),
-        _ => Some(String::from("no exif data")),
-    };
-    datetime
-}
 
-#[cfg(test)]
-mod tests {
-    use tempfile::TempDir;
+    // The source as it is recorded, under its archive or camera when staged
+    fn label(&self, source: &str) -> String {
+        staged_label(source, self.staged.as_ref())
+    }
 
-    use super::*;
+    fn report(&mut self, source: &str, target: &str, entry: &MediaEntry, status: Status) {
+        let label = self.label(source);
+        if let Some(report) = &mut self.report {
+            report.add(&label, target, entry.date_source, status);
+        }
+    }
 
-    macro_rules! test_case {
-        ($fname:expr) => {
-            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/", $fname)
-        };
+    fn planned(&mut self, source: &str, target: &str, entry: &MediaEntry) {
+        self.report(source, target, entry, Status::Planned);
+        self.summary.copied += 1;
+        self.summary.bytes += file_size(source);
     }
 
-    #[test]
-    fn test_is_media_file() {
-        let list_of_media_files = vec!["jpg", "jpeg", "mp4", "png", "JPG", "JPEG", "MP4", "PNG"];
-        for media_file in list_of_media_files {
-            let filename = format!("test.{}", media_file);
-            assert_eq!(
-                true,
-                is_media_file(Path::new(&filename)),
-                "File should be a media file {}",
-                filename
-            );
+    fn copied(&mut self, source: &str, target: &str, entry: &MediaEntry) {
+        self.report(source, target, entry, Status::Copied);
+        self.summary.copied += 1;
+        self.summary.bytes += file_size(source);
+        let label = self.label(source);
+        if let Some(copied) = &mut self.copied {
+            copied.push((label, PathBuf::from(target)));
         }
     }
 
-    #[test]
-    fn test_read_jpg_exif() {
-        let filename = test_case!("test_image.JPG");
-        assert_eq!(
-            Some(String::from(format!("2022/12/17/test_image.JPG"))),
-            read_jpg_exif(filename)
-        );
+    fn skipped(&mut self, source: &str, target: &str, entry: &MediaEntry) {
+        self.report(source, target, entry, Status::Skipped);
+        self.summary.skipped += 1;
     }
 
-    #[test]
-    fn read_no_smartphone_image() {
-        let filename = "no_match.jpg";
-        assert_eq!(None, smartphone_file(filename));
-    }
-    #[test]
-    fn read_smartphone_video() {
-        let filename = "VID_20221220_170102.jpg";
-        assert_eq!(
-            Some(String::from(format!("2022/12/20/{filename}"))),
-            smartphone_file(filename)
-        );
+    fn in_place(&mut self, source: &str, target: &str, entry: &MediaEntry) {
+        self.report(source, target, entry, Status::InPlace);
+        self.summary.in_place += 1;
     }
-    #[test]
-    fn read_smartphone_image() {
-        let filename = "IMG_20230115_102911.jpg";
-        assert_eq!(
-            Some(String::from(format!("2023/01/15/{filename}"))),
-            smartphone_file(filename)
-        );
+
+    fn errored(&mut self, source: &str, target: &str, entry: &MediaEntry, error: &dyn Error) {
+        let label = self.label(source);
+        if let Some(report) = &mut self.report {
+            report.add_error(&label, target, entry.date_source, error.to_string());
+        }
+        self.summary.errored += 1;
     }
+}
 
-    #[test]
-    fn find_all_media_files_recursive() {
-        let tmpdir = TempDir::new().unwrap();
-        let test_images = tmpdir.path().join("test_images");
-        //let target_images = tmpdir.path().join("target_images");
-        create_dir(test_images.to_str().unwrap()).unwrap();
-        let test_media_files = [
-            "IMG_20210130_000001.jpg",
-            "IMG_20210130_000002.jpg",
-            "VID_20210130_000003.mp4",
-        ];
-        for file in test_media_files.iter() {
-            fs::File::create(test_images.join(file)).expect("Just create the test files");
+#[derive(Debug)]
+pub struct Options {
+    pub recursive: bool,
+    pub skip_empty_dirs: bool,
+    pub dry_run: bool,
+    pub report: Option<PathBuf>,
+    pub write_index: bool,
+    pub dedupe_keep_original_location: bool,
+    pub by_serial: bool,
+    pub by_lens: bool,
+    pub by_aspect: bool,
+    pub aspect_videos: bool,
+    pub split_by_ext: bool,
+    pub by_person: bool,
+    pub person_rule: PersonRule,
+    pub retry_locked: bool,
+    pub strategy_order: Vec<Strategy>,
+    pub verify_size: bool,
+    pub verify_all: bool,
+    pub dedup_inodes: bool,
+    pub on_conflict: ConflictPolicy,
+    pub collision_log: Option<PathBuf>,
+    pub motion_photos: bool,
+    pub summary_json: bool,
+    pub video_target: Option<PathBuf>,
+    pub screenshots_target: Option<PathBuf>,
+    pub quarantine_corrupt: bool,
+    pub strict_formats: bool,
+    pub category_targets: Vec<(String, PathBuf)>,
+    pub min_age: Option<Duration>,
+    pub granularity: Granularity,
+    pub with_weekday: bool,
+    pub weekday_names: Vec<String>,
+    pub sequence_scope: Option<SequenceScope>,
+    pub layout: Option<Layout>,
+    pub merge_into_existing_structure: bool,
+    pub preserve_xattr: bool,
+    pub dedup_identical: bool,
+    pub move_files: bool,
+    pub checksum_algo: ChecksumAlgo,
+    /// Extensions whose filename dates are trusted, all of them if empty
+    pub filename_date_ext: Vec<String>,
+    pub catch_panics: bool,
+    pub sidecars: bool,
+    pub sync_sidecars: bool,
+    pub min_resolution: Option<(u32, u32)>,
+    pub symlink: bool,
+    pub relative_links: bool,
+    pub resume_cursor: Option<PathBuf>,
+    pub max_files_in_memory: Option<usize>,
+    pub copy_attributes: bool,
+    pub in_place_rename: bool,
+    pub oldest_date: bool,
+    pub day_cutoff: Option<NaiveTime>,
+    pub contact_sheet: bool,
+    pub check_free_space: bool,
+    pub wait_on_disk_full: bool,
+    pub atomic: bool,
+    pub camera_offsets: Vec<(String, TimeDelta)>,
+    pub scan_threads: Option<usize>,
+    pub mmap_exif: bool,
+    pub scan_cache: Option<PathBuf>,
+    pub cas: Option<PathBuf>,
+    pub image_date: Vec<Strategy>,
+    pub video_date: Vec<Strategy>,
+    pub download_placeholders: bool,
+    pub run_log: Option<PathBuf>,
+    pub exposure_filter: ExposureFilter,
+    pub skip_hashes: HashSet<String>,
+    pub temp_dir: Option<PathBuf>,
+    pub normalize_unicode: bool,
+    pub write_exif_date: bool,
+    pub catalog: Option<PathBuf>,
+    pub progress_eta: bool,
+    pub archive_before: Option<NaiveDate>,
+    pub archive_target: Option<PathBuf>,
+    pub date_command: Option<String>,
+    pub date_command_timeout: Duration,
+    pub source_label: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            skip_empty_dirs: false,
+            dry_run: false,
+            report: None,
+            write_index: false,
+            dedupe_keep_original_location: false,
+            by_serial: false,
+            by_lens: false,
+            by_aspect: false,
+            aspect_videos: false,
+            split_by_ext: false,
+            by_person: false,
+            person_rule: PersonRule::First,
+            retry_locked: false,
+            strategy_order: Strategy::DEFAULT_ORDER.to_vec(),
+            verify_size: false,
+            verify_all: false,
+            dedup_inodes: false,
+            on_conflict: ConflictPolicy::Rename,
+            collision_log: None,
+            motion_photos: false,
+            summary_json: false,
+            video_target: None,
+            screenshots_target: None,
+            quarantine_corrupt: false,
+            strict_formats: false,
+            category_targets: Vec::new(),
+            min_age: None,
+            granularity: Granularity::Day,
+            with_weekday: false,
+            weekday_names: Vec::new(),
+            sequence_scope: None,
+            layout: None,
+            merge_into_existing_structure: false,
+            preserve_xattr: false,
+            dedup_identical: false,
+            move_files: false,
+            checksum_algo: ChecksumAlgo::Sha256,
+            filename_date_ext: Vec::new(),
+            catch_panics: false,
+            sidecars: false,
+            sync_sidecars: false,
+            min_resolution: None,
+            symlink: false,
+            relative_links: false,
+            resume_cursor: None,
+            max_files_in_memory: None,
+            copy_attributes: false,
+            in_place_rename: false,
+            oldest_date: false,
+            day_cutoff: None,
+            contact_sheet: false,
+            check_free_space: false,
+            wait_on_disk_full: false,
+            atomic: false,
+            camera_offsets: Vec::new(),
+            scan_threads: None,
+            mmap_exif: false,
+            scan_cache: None,
+            cas: None,
+            image_date: Vec::new(),
+            video_date: Vec::new(),
+            download_placeholders: false,
+            run_log: None,
+            exposure_filter: ExposureFilter::default(),
+            skip_hashes: HashSet::new(),
+            temp_dir: None,
+            normalize_unicode: false,
+            write_exif_date: false,
+            catalog: None,
+            progress_eta: false,
+            archive_before: None,
+            archive_target: None,
+            date_command: None,
+            date_command_timeout: Duration::from_secs(10),
+            source_label: None,
         }
+    }
+}
 
-        let mut mediaconfig = MediaConfig::new(
-            test_images.to_str().unwrap().to_string(),
-            tmpdir.path().join("target_images"),
-        );
-        mediaconfig
-            .find_all_media_files(Some(tmpdir.path().to_str().unwrap()), true)
-            .expect("Everything works as intended");
-        assert_eq!(test_media_files.len(), mediaconfig.files.len());
+#[derive(Debug)]
+pub struct MediaConfig {
+    pub source: String,
+    pub target: PathBuf,
+    pub options: Options,
+    files: HashMap<String, MediaEntry>,
+    seen_files: HashSet<(u64, u64)>,
+    unsupported: Vec<PathBuf>,
+    scan_cache: Option<ScanCache>,
+    stop: Arc<AtomicBool>,
+}
 
-        let targets: Vec<String> = mediaconfig.files.into_values().collect();
-        for file in test_media_files.iter() {
-            assert!(targets.contains(&String::from(format!("2021/01/30/{file}"))));
+impl MediaConfig {
+    pub fn new(source: String, target: PathBuf) -> Self {
+        Self {
+            source,
+            target,
+            options: Options::default(),
+            files: HashMap::new(),
+            seen_files: HashSet::new(),
+            unsupported: Vec::new(),
+            scan_cache: None,
+            stop: Arc::new(AtomicBool::new(false)),
         }
+    }
 
-        tmpdir.close().expect("Remove test directory");
+    /// Setting the returned flag stops copying after the current file
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    pub fn copy_media_files(&mut self) -> Result<CopySummary, Box<dyn Error>> {
+        let started = Instant::now();
+        if self.options.dedup_inodes && !cfg!(unix) {
+            warn!("--dedup-inodes is only supported on Unix, ignoring it");
+        }
+        if self.options.preserve_xattr && !cfg!(unix) {
+            warn!("--preserve-xattr is only supported on Unix, ignoring it");
+        }
+        if self.options.copy_attributes && !cfg!(unix) {
+            warn!("--copy-attributes is only supported on Unix, ignoring it");
+        }
+        if self.options.catalog.is_some() {
+            catalog::ensure_supported()?;
+        }
+        if let Some(temp_dir) = self
+            .options
+            .temp_dir
+            .as_ref()
+            .filter(|_| self.options.atomic)
+        {
+            create_dir(temp_dir.to_str().ok_or("--temp-dir is not valid UTF-8")?)?;
+            if same_filesystem(temp_dir, &self.target) == Some(false) {
+                warn!(
+                    "--temp-dir {} is on another filesystem than the target, copies are not atomic",
+                    temp_dir.display()
+                );
+            }
+        }
+        if self.options.merge_into_existing_structure {
+            self.adopt_existing_granularity();
+        }
+        let mut tally = Tally::new(&self.options, self.staged());
+        if self.options.in_place_rename {
+            self.find_all_media_files(None, self.options.recursive)?;
+            self.rename_in_place(&mut tally)?;
+            return self.finish(tally, started);
+        }
+        let mut collisions = match &self.options.collision_log {
+            Some(path) => CollisionLog::open(path)?,
+            None => CollisionLog::default(),
+        };
+        match (
+            self.options.resume_cursor.clone(),
+            self.options.max_files_in_memory,
+        ) {
+            (Some(cursor), _) => self.copy_in_chunks(&cursor, &mut tally, &mut collisions)?,
+            (None, Some(max_files)) => {
+                self.copy_in_batches(max_files, &mut tally, &mut collisions)?
+            }
+            (None, None) => {
+                self.find_all_media_files(None, self.options.recursive)?;
+                self.copy_found(&mut tally, &mut collisions)?;
+            }
+        }
+        self.finish(tally, started)
+    }
+
+    // Log the totals and write the indexes and report of a run
+    fn finish(&self, tally: Tally, started: Instant) -> Result<CopySummary, Box<dyn Error>> {
+        let Tally {
+            mut summary,
+            report,
+            copied,
+            cataloged,
+            hashed,
+            aliases,
+            ..
+        } = tally;
+        // with --summary-json the caller prints the totals instead
+        if !self.options.summary_json {
+            if self.options.dry_run {
+                info!("Would copy {}/{} files", summary.copied, summary.found);
+            } else if self.options.in_place_rename {
+                info!("Renamed {}/{} files", summary.copied, summary.found);
+            } else if self.options.move_files {
+                info!("Moved {}/{} files", summary.copied, summary.found);
+            } else {
+                info!("Copied {}/{} files", summary.copied, summary.found);
+            }
+        }
+        let copied = copied.unwrap_or_default();
+        if self.options.write_index {
+            index::write_indexes(&copied)?;
+            if !aliases.is_empty() {
+                index::record_aliases(&aliases)?;
+            }
+        }
+        if self.options.contact_sheet {
+            contact_sheet::write_contact_sheets(&copied);
+        }
+        if let Some(database) = &self.options.catalog {
+            catalog::record(
+                database,
+                &cataloged,
+                self.options.checksum_algo,
+                self.options.mmap_exif,
+            )?;
+        }
+        if let (Some(path), Some(mut report)) = (&self.options.report, report) {
+            report.write(path)?;
+        }
+        if self.options.verify_all && !self.options.dry_run {
+            info!("Verifying {} copies against their sources", hashed.len());
+            let mismatches = verify::mismatches(&hashed, self.options.checksum_algo);
+            let result = verify::summary(hashed.len(), &mismatches);
+            if mismatches.is_empty() {
+                info!("{}", result.trim_end());
+            } else {
+                error!("{}", result.trim_end());
+            }
+            summary.verify_mismatches = Some(mismatches.len());
+        }
+        summary.elapsed_secs = started.elapsed().as_secs_f64();
+        if let Some(path) = &self.options.run_log {
+            report::append_run_log(path, &summary, self.options.dry_run)?;
+        }
+        Ok(summary)
+    }
+
+    // Give the found files normalized names in the folder they are in
+    fn rename_in_place(&mut self, tally: &mut Tally) -> Result<(), Box<dyn Error>> {
+        self.check_formats()?;
+        tally.summary.found = self.files.len();
+        let mut planned = HashSet::new();
+        let mut sources: Vec<&String> = self.files.keys().collect();
+        sources.sort();
+        for source in sources {
+            let entry = &self.files[source];
+            let Some(time) = rename::capture_time(
+                source,
+                strategy_order(source, &self.options),
+                self.options.mmap_exif,
+            ) else {
+                info!("Skipping {}, no capture time", source);
+                tally.skipped(source, source, entry);
+                continue;
+            };
+            let mut target = rename::new_name(Path::new(source), time);
+            if target == Path::new(source) {
+                tally.in_place(source, source, entry);
+                planned.insert(target);
+                continue;
+            }
+            if target.exists() || planned.contains(&target) {
+                match self.options.on_conflict {
+                    ConflictPolicy::Rename => {
+                        match identical_copy(source, &target, self.options.checksum_algo) {
+                            // already renamed by an earlier run
+                            Some(existing) if existing == Path::new(source) => {
+                                tally.in_place(source, source, entry);
+                                planned.insert(existing);
+                                continue;
+                            }
+                            Some(existing) => {
+                                info!("Skipping {}, identical to {}", source, existing.display());
+                                tally.skipped(source, existing.to_str().unwrap(), entry);
+                                continue;
+                            }
+                            None => target = conflict::free_name(&target, &planned),
+                        }
+                    }
+                    policy if policy.overwrites(Path::new(source), &target) => {}
+                    _ => {
+                        warn!("Skipping {}, {} already exists", source, target.display());
+                        tally.skipped(source, target.to_str().unwrap(), entry);
+                        continue;
+                    }
+                }
+            }
+            planned.insert(target.clone());
+            let target = target.to_str().unwrap();
+            if self.options.dry_run {
+                info!("Would rename {} to {}", source, target);
+                tally.planned(source, target, entry);
+                continue;
+            }
+            info!("Rename {} to {}", source, target);
+            match fs::rename(long_path(Path::new(source)), long_path(Path::new(target))) {
+                Ok(()) => {
+                    tally.copied(source, target, entry);
+                    // the source is gone, so its size is taken from the new name
+                    tally.summary.bytes += file_size(target);
+                }
+                Err(e) => {
+                    error!("Error renaming file: {}", e);
+                    tally.errored(source, target, entry, &e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Copy the files found by the last scan
+    fn copy_found(
+        &mut self,
+        tally: &mut Tally,
+        collisions: &mut CollisionLog,
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_formats()?;
+        if self.options.motion_photos {
+            motion::pair_live_photos(&mut self.files);
+        }
+        info!("Found {} files", self.files.len());
+        tally.summary.found += self.files.len();
+        if self.options.check_free_space && !self.options.dry_run && !self.options.symlink {
+            self.check_free_space()?;
+        }
+        let mut locked = Vec::new();
+        let mut planned = HashSet::new();
+        let duplicates = if self.options.dedup_identical {
+            self.identical_duplicates()
+        } else {
+            HashSet::new()
+        };
+        if let Some(scope) = self.options.sequence_scope {
+            self.number_sequences(scope, &duplicates);
+        }
+        let mut sources: Vec<&String> = self.files.keys().collect();
+        sources.sort();
+        let mut progress = self.options.progress_eta.then(|| {
+            let bytes = sources.iter().map(|source| file_size(source)).sum();
+            Progress::new(sources.len(), bytes)
+        });
+        for source in sources {
+            if let Some(progress) = &mut progress {
+                progress.next(file_size(source));
+            }
+            if self.stop.load(Ordering::SeqCst) {
+                warn!("Interrupted, not copying the remaining files");
+                tally.summary.interrupted = true;
+                break;
+            }
+            let entry = &self.files[source];
+            let mut target = self.target_root(source, entry).join(&entry.target);
+            if duplicates.contains(source) {
+                tally.skipped(source, target.to_str().unwrap(), entry);
+                continue;
+            }
+            if is_same_file(source, &target) {
+                debug!("File {} already is at its target", source);
+                tally.in_place(source, target.to_str().unwrap(), entry);
+                planned.insert(target);
+                continue;
+            }
+            if self.options.on_conflict == ConflictPolicy::Rename
+                && (target.exists() || planned.contains(&target))
+            {
+                match identical_copy(source, &target, self.options.checksum_algo) {
+                    // renamed into place by an earlier run
+                    Some(existing) if is_same_file(source, &existing) => {
+                        debug!("File {} already is at its target", source);
+                        tally.in_place(source, existing.to_str().unwrap(), entry);
+                        planned.insert(existing);
+                        continue;
+                    }
+                    // skipped as an existing file below
+                    Some(existing) => target = existing,
+                    None => {
+                        let renamed = conflict::free_name(&target, &planned);
+                        if self.options.dry_run {
+                            info!("Would rename {} to {}", target.display(), renamed.display());
+                        } else {
+                            collisions.record(source, &target, &renamed)?;
+                        }
+                        target = renamed;
+                    }
+                }
+            }
+            planned.insert(target.clone());
+            let target = target.to_str().unwrap();
+            if self.options.dry_run {
+                if Path::new(target).exists()
+                    && !self
+                        .options
+                        .on_conflict
+                        .overwrites(Path::new(source), Path::new(target))
+                {
+                    info!("Would skip file {}, already exists", target);
+                    tally.skipped(source, target, entry);
+                } else {
+                    info!(
+                        "Would copy file {} to {} ({})",
+                        source,
+                        target,
+                        entry.origin()
+                    );
+                    tally.planned(source, target, entry);
+                }
+                continue;
+            }
+            let cataloged = self.catalog_entry(source, target, entry);
+            let hashed = self.source_hash(source, target);
+            // an atomic copy cleans up after itself, and a file that was there
+            // before is never removed
+            let partial_copy = !self.options.atomic && !long_path(Path::new(target)).exists();
+            let result = loop {
+                match copy_file(source, target, &self.options) {
+                    Err(e) if is_disk_full(e.as_ref()) => {
+                        // do not leave a truncated copy behind
+                        if partial_copy {
+                            let _ = fs::remove_file(long_path(Path::new(target)));
+                        }
+                        if !self.options.wait_on_disk_full
+                            || !wait_for_space(target)?
+                            || self.stop.load(Ordering::SeqCst)
+                        {
+                            break Err(e);
+                        }
+                    }
+                    result => break result,
+                }
+            };
+            match result {
+                Ok(true) => {
+                    tally.copied(source, target, entry);
+                    tally.cataloged.extend(cataloged);
+                    tally.hashed.extend(hashed);
+                    self.copy_sidecars(source, target, false);
+                }
+                Ok(false) => {
+                    tally.skipped(source, target, entry);
+                    self.note_alias(source, target, tally);
+                    if self.options.sync_sidecars {
+                        self.copy_sidecars(source, target, true);
+                    }
+                }
+                Err(e) if self.options.retry_locked && is_locked(e.as_ref()) => {
+                    warn!("File {} is locked, retrying later: {}", source, e);
+                    locked.push((source, target.to_string()));
+                }
+                Err(e) if is_disk_full(e.as_ref()) => {
+                    error!("Target is full, stopping: {}", e);
+                    tally.errored(source, target, entry, e.as_ref());
+                    tally.summary.interrupted = true;
+                    break;
+                }
+                Err(e) => {
+                    error!("Error copying file: {}", e);
+                    tally.errored(source, target, entry, e.as_ref());
+                }
+            }
+        }
+        if let Some(progress) = &mut progress {
+            progress.finish();
+        }
+        if !locked.is_empty() && !tally.summary.interrupted {
+            info!("Retrying {} locked files", locked.len());
+            thread::sleep(RETRY_LOCKED_DELAY);
+            for (source, target) in locked {
+                let entry = &self.files[source];
+                let cataloged = self.catalog_entry(source, &target, entry);
+                let hashed = self.source_hash(source, &target);
+                match copy_file(source, &target, &self.options) {
+                    Ok(true) => {
+                        info!("Copied previously locked file {}", source);
+                        tally.copied(source, &target, entry);
+                        tally.cataloged.extend(cataloged);
+                        tally.hashed.extend(hashed);
+                        self.copy_sidecars(source, &target, false);
+                    }
+                    Ok(false) => {
+                        tally.skipped(source, &target, entry);
+                        self.note_alias(source, &target, tally);
+                    }
+                    Err(e) => {
+                        error!("Error copying locked file: {}", e);
+                        tally.errored(source, &target, entry, e.as_ref());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The temporary folder a staged source was extracted or downloaded to,
+    // together with the name it is recorded under
+    fn staged(&self) -> Option<(String, String)> {
+        let label = self.options.source_label.clone()?;
+        Some((self.source.clone(), label))
+    }
+
+    // With --dedupe-keep-original-location remember a skipped source as an
+    // alias of its target when both have the same content
+    fn note_alias(&self, source: &str, target: &str, tally: &mut Tally) {
+        if self.options.dedupe_keep_original_location
+            && same_content(source, target, self.options.checksum_algo)
+        {
+            debug!("{} is a duplicate of {}", source, target);
+            tally
+                .aliases
+                .push((tally.label(source), PathBuf::from(target)));
+        }
+    }
+
+    // With --verify-all the hash a file about to be copied should have,
+    // taken before the copy since a moved source is gone afterwards. When
+    // --write-exif-date adds a date to the copy it is added here as well.
+    fn source_hash(&self, source: &str, target: &str) -> Option<verify::Recorded> {
+        if !self.options.verify_all {
+            return None;
+        }
+        let algo = self.options.checksum_algo;
+        let path = long_path(Path::new(source));
+        let hash = match exif_date_to_write(source, &self.options) {
+            Some(time) => fs::read(&path).map(|content| {
+                let written = exif_date::with_date_time_original(&content, time);
+                checksum::bytes_digest(written.as_deref().unwrap_or(&content), algo)
+            }),
+            None => checksum::file_digest(&path, algo),
+        };
+        match hash {
+            Ok(hash) => Some((PathBuf::from(target), hash)),
+            Err(e) => {
+                warn!("Could not hash {}, it is not verified: {}", source, e);
+                None
+            }
+        }
+    }
+
+    // The catalog row of a file about to be copied. Its date is the one stored
+    // with the entry when it was dated, since the target may have other folders
+    // than dates and the source may be gone after a move.
+    fn catalog_entry(
+        &self,
+        source: &str,
+        target: &str,
+        entry: &MediaEntry,
+    ) -> Option<catalog::Entry> {
+        self.options.catalog.as_ref()?;
+        let date = entry.date.as_ref().map(|date| date.replace('/', "-"));
+        Some(catalog::Entry {
+            source: staged_label(source, self.staged().as_ref()),
+            target: PathBuf::from(target),
+            date,
+            date_source: entry.date_source,
+        })
+    }
+
+    // Scan and copy one folder at a time, keeping only its files in memory.
+    // The last finished folder is written to the cursor file, so a run that
+    // was interrupted resumes after it. The cursor is removed once done.
+    fn copy_in_chunks(
+        &mut self,
+        cursor: &Path,
+        tally: &mut Tally,
+        collisions: &mut CollisionLog,
+    ) -> Result<(), Box<dyn Error>> {
+        let done = chunk::read_cursor(cursor)?;
+        if let Some(done) = &done {
+            info!("Resuming after {}", done.display());
+        }
+        let folders = chunk::folders(
+            Path::new(&self.source),
+            self.options.recursive,
+            self.options.skip_empty_dirs,
+        )?;
+        for folder in folders {
+            if done.as_ref().is_some_and(|done| &folder <= done) {
+                continue;
+            }
+            let Some(name) = folder.to_str() else {
+                warn!("Skipping {}, its name is not valid UTF-8", folder.display());
+                continue;
+            };
+            let mut candidates = Vec::new();
+            self.collect_candidates(name, false, &mut candidates)?;
+            let batch_size = self.options.max_files_in_memory.unwrap_or(candidates.len());
+            for batch in candidates.chunks(batch_size.max(1)) {
+                self.copy_batch(batch, tally, collisions)?;
+                if tally.summary.interrupted {
+                    return Ok(());
+                }
+            }
+            chunk::write_cursor(cursor, &folder)?;
+        }
+        fs::remove_file(cursor)?;
+        Ok(())
+    }
+
+    // Scan the folders one at a time and copy the files found so far whenever
+    // max_files are pending, so at most that many are kept in memory however
+    // large the library is. Duplicates and live photo pairs are only detected
+    // within a batch.
+    fn copy_in_batches(
+        &mut self,
+        max_files: usize,
+        tally: &mut Tally,
+        collisions: &mut CollisionLog,
+    ) -> Result<(), Box<dyn Error>> {
+        let folders = chunk::folders(
+            Path::new(&self.source),
+            self.options.recursive,
+            self.options.skip_empty_dirs,
+        )?;
+        let mut pending = Vec::new();
+        for folder in folders {
+            let Some(name) = folder.to_str() else {
+                warn!("Skipping {}, its name is not valid UTF-8", folder.display());
+                continue;
+            };
+            self.collect_candidates(name, false, &mut pending)?;
+            while pending.len() >= max_files {
+                let batch: Vec<PathBuf> = pending.drain(..max_files).collect();
+                self.copy_batch(&batch, tally, collisions)?;
+                if tally.summary.interrupted {
+                    return Ok(());
+                }
+            }
+        }
+        if !pending.is_empty() {
+            self.copy_batch(&pending, tally, collisions)?;
+        }
+        Ok(())
+    }
+
+    // Scan and copy one batch of candidates, forgetting the previous one
+    fn copy_batch(
+        &mut self,
+        batch: &[PathBuf],
+        tally: &mut Tally,
+        collisions: &mut CollisionLog,
+    ) -> Result<(), Box<dyn Error>> {
+        self.files.clear();
+        self.scan_candidates(batch)?;
+        self.copy_found(tally, collisions)
+    }
+
+    // With --strict-formats fail on the media files found that this build
+    // cannot sort, before copying any of the found files
+    fn check_formats(&self) -> Result<(), Box<dyn Error>> {
+        if self.unsupported.is_empty() {
+            return Ok(());
+        }
+        for path in &self.unsupported {
+            error!("Unsupported format: {}", path.display());
+        }
+        Err(format!(
+            "found {} media files of a format this build cannot sort",
+            self.unsupported.len()
+        )
+        .into())
+    }
+
+    // Make sure every target root has room for the files still to be copied
+    // into it, instead of filling the disk halfway through
+    fn check_free_space(&self) -> Result<(), Box<dyn Error>> {
+        let mut needed: BTreeMap<&Path, u64> = BTreeMap::new();
+        for (source, entry) in &self.files {
+            let root = self.target_root(source, entry);
+            if !root.join(&entry.target).exists() {
+                *needed.entry(root).or_default() += file_size(source);
+            }
+        }
+        for (root, needed) in needed {
+            // the root may not exist yet, ask the closest folder that does
+            let existing = root
+                .ancestors()
+                .find(|folder| folder.exists())
+                .unwrap_or(root);
+            let available = fs4::available_space(existing)?;
+            enough_space(root, needed, available)?;
+        }
+        Ok(())
+    }
+
+    // Copy the sidecars of a file next to its target. Existing sidecars are
+    // only replaced when syncing the ones of skipped files.
+    fn copy_sidecars(&self, source: &str, target: &str, sync: bool) {
+        if !self.options.sidecars {
+            return;
+        }
+        for (sidecar, destination) in xmp::sidecar_targets(Path::new(source), Path::new(target)) {
+            if destination.exists() && !sync {
+                continue;
+            }
+            info!(
+                "Copy sidecar {} to {}",
+                sidecar.display(),
+                destination.display()
+            );
+            if let Err(e) = fs::copy(long_path(&sidecar), long_path(&destination)) {
+                error!("Error copying sidecar {}: {}", sidecar.display(), e);
+            }
+        }
+    }
+
+    // The sources with the same content as another one that is kept. Only
+    // files of equal size are hashed. Of identical files the first source by
+    // name is kept, so the choice does not depend on the scan order. Files
+    // that only share a target name are left to --on-conflict.
+    fn identical_duplicates(&self) -> HashSet<String> {
+        let mut by_size: BTreeMap<u64, Vec<&String>> = BTreeMap::new();
+        for source in self.files.keys() {
+            by_size.entry(file_size(source)).or_default().push(source);
+        }
+        let mut by_hash: BTreeMap<Vec<u8>, Vec<&String>> = BTreeMap::new();
+        for sources in by_size.into_values().filter(|sources| sources.len() > 1) {
+            for source in sources {
+                match checksum::file_digest(
+                    &long_path(Path::new(source)),
+                    self.options.checksum_algo,
+                ) {
+                    Ok(hash) => by_hash.entry(hash).or_default().push(source),
+                    Err(e) => warn!("Could not hash {}, it is kept: {}", source, e),
+                }
+            }
+        }
+        let mut duplicates = HashSet::new();
+        for mut sources in by_hash.into_values() {
+            if sources.len() < 2 {
+                continue;
+            }
+            sources.sort();
+            info!(
+                "Keeping {} of {} files with the same content",
+                sources[0],
+                sources.len()
+            );
+            duplicates.extend(sources[1..].iter().map(|source| source.to_string()));
+        }
+        duplicates
+    }
+
+    // Prefix the targets with a sequence number. The files of a day, a target
+    // folder or a source folder (--sequence-scope event) are counted in the
+    // order of their names, which follows the capture time for the names of
+    // cameras and phones. A file copied by an earlier run keeps its number, new
+    // files continue after the highest number of their day or folder.
+    fn number_sequences(&mut self, scope: SequenceScope, skipped: &HashSet<String>) {
+        let mut numbered: HashMap<PathBuf, Vec<(u32, PathBuf)>> = HashMap::new();
+        // the folder of a counter, with the day for --sequence-scope day
+        let mut groups: BTreeMap<(PathBuf, Option<String>), Vec<_>> = BTreeMap::new();
+        for (source, entry) in &self.files {
+            if skipped.contains(source) {
+                continue;
+            }
+            let target = self.target_root(source, entry).join(&entry.target);
+            let folder = target.parent().map(Path::to_path_buf).unwrap_or_default();
+            let existing = numbered
+                .entry(folder.clone())
+                .or_insert_with(|| numbered_files(&folder));
+            let copied = existing
+                .iter()
+                .find(|(_, path)| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .and_then(|name| name.split_once('_'))
+                        .is_some_and(|(_, name)| name == file_name(&entry.target))
+                        && same_content(source, path.to_str().unwrap(), self.options.checksum_algo)
+                })
+                .map(|(number, _)| *number);
+            let key = match scope {
+                SequenceScope::Day => (folder, entry.date.clone()),
+                SequenceScope::Folder => (folder, None),
+                SequenceScope::Event => {
+                    let event = Path::new(source).parent().map(Path::to_path_buf);
+                    (event.unwrap_or_default(), None)
+                }
+            };
+            groups
+                .entry(key)
+                .or_default()
+                .push((source.clone(), copied));
+        }
+        for ((folder, day), mut sources) in groups {
+            let before = match scope {
+                SequenceScope::Day => numbered[&folder]
+                    .iter()
+                    .filter(|(_, path)| {
+                        path.to_str()
+                            .and_then(|path| dated_path(path, &self.options))
+                            .and_then(|entry| entry.date)
+                            == day
+                    })
+                    .map(|(number, _)| *number)
+                    .max(),
+                SequenceScope::Folder => numbered[&folder].iter().map(|(number, _)| *number).max(),
+                SequenceScope::Event => None,
+            };
+            let mut last = sources
+                .iter()
+                .filter_map(|(_, copied)| *copied)
+                .chain(before)
+                .max()
+                .unwrap_or(0);
+            sources.sort_by_key(|(source, _)| {
+                (
+                    file_name(&self.files[source].target).to_string(),
+                    source.clone(),
+                )
+            });
+            for (source, copied) in sources {
+                let number = copied.unwrap_or_else(|| {
+                    last += 1;
+                    last
+                });
+                let entry = self.files.get_mut(&source).unwrap();
+                let name = file_name(&entry.target).to_string();
+                let folder = &entry.target[..entry.target.len() - name.len()];
+                entry.target = format!("{}{:04}_{}", folder, number, name);
+            }
+        }
+    }
+
+    // Use the granularity of the date folders already in the target
+    fn adopt_existing_granularity(&mut self) {
+        match layout::detect_granularity(&self.target) {
+            Some(granularity) => {
+                info!(
+                    "Target {} uses {:?} folders, sorting new files the same way",
+                    self.target.display(),
+                    granularity
+                );
+                self.options.granularity = granularity;
+            }
+            None => debug!(
+                "Could not detect the layout of {}, using {:?}",
+                self.target.display(),
+                self.options.granularity
+            ),
+        }
+    }
+
+    // Scan without copying and try every strategy on the first files, to
+    // suggest the strategy order for a new library
+    pub fn suggest_config(&mut self, sample: usize) -> Result<String, Box<dyn Error>> {
+        self.find_all_media_files(None, self.options.recursive)?;
+        let mut sources: Vec<&String> = self.files.keys().collect();
+        sources.sort();
+        sources.truncate(sample);
+        let matches: Vec<(Strategy, usize)> = Strategy::DEFAULT_ORDER
+            .iter()
+            .map(|strategy| {
+                let count = sources
+                    .iter()
+                    .filter(|source| strategy.target_path(source, &self.options).is_some())
+                    .count();
+                (*strategy, count)
+            })
+            .collect();
+        Ok(suggest::suggestion(sources.len(), &matches))
+    }
+
+    // Scan without copying and compare the planned targets with a reference
+    // tree, matching the files by their content
+    pub fn compare_with_reference(&mut self, reference: &Path) -> Result<String, Box<dyn Error>> {
+        self.find_all_media_files(None, self.options.recursive)?;
+        let mut planned: Vec<(String, PathBuf)> = self
+            .files
+            .iter()
+            .map(|(source, entry)| (source.clone(), PathBuf::from(&entry.target)))
+            .collect();
+        planned.sort();
+        let index = reference::index(reference, self.options.checksum_algo)
+            .map_err(|e| format!("cannot read {}: {}", reference.display(), e))?;
+        Ok(reference::compare(
+            &planned,
+            &index,
+            self.options.checksum_algo,
+        )?)
+    }
+
+    // Scan without copying and write the metadata of every file to a CSV
+    pub fn write_exif_csv(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.find_all_media_files(None, self.options.recursive)?;
+        let mut sources: Vec<&String> = self.files.keys().collect();
+        sources.sort();
+        let rows: Vec<exif_csv::Row> = sources
+            .into_iter()
+            .map(|source| {
+                let exif = open_exif(source, self.options.mmap_exif);
+                let field = |tag| {
+                    exif.as_ref()
+                        .and_then(|exif| ascii_field(exif, tag))
+                        .unwrap_or_default()
+                };
+                let entry = &self.files[source];
+                let date = entry
+                    .date
+                    .as_ref()
+                    .map(|date| date.replace('/', "-"))
+                    .unwrap_or_default();
+                let strategy = entry.date_source.map(|s| s.to_string()).unwrap_or_default();
+                exif_csv::Row {
+                    path: source.clone(),
+                    date,
+                    make: field(Tag::Make),
+                    model: field(Tag::Model),
+                    strategy,
+                }
+            })
+            .collect();
+        exif_csv::write(path, &rows)?;
+        info!(
+            "Wrote metadata of {} files to {}",
+            rows.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    // The root directory a file is sorted into. Files older than --archive-before
+    // go to the archive, then screenshots to --screenshots-target, otherwise a
+    // mapping for its extension wins over one for its media category, which wins
+    // over --video-target
+    fn target_root(&self, source: &str, entry: &MediaEntry) -> &Path {
+        if let Some(archive) = self.archive_root(entry) {
+            return archive;
+        }
+        if let Some(screenshots) = &self.options.screenshots_target {
+            if is_screenshot(source, self.options.mmap_exif) {
+                return screenshots;
+            }
+        }
+        let path = Path::new(source);
+        let category = media_category(path);
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        let mapped = |key: &str| {
+            self.options
+                .category_targets
+                .iter()
+                .find(|(mapped, _)| mapped == key)
+                .map(|(_, root)| root.as_path())
+        };
+        extension
+            .and_then(|ext| mapped(&ext))
+            .or_else(|| {
+                category
+                    .and_then(|category| category.to_possible_value())
+                    .and_then(|category| mapped(category.get_name()))
+            })
+            .or(match category {
+                Some(MediaCategory::Video) => self.options.video_target.as_deref(),
+                _ => None,
+            })
+            .unwrap_or(&self.target)
+    }
+
+    // The archive root for files dated before --archive-before
+    fn archive_root(&self, entry: &MediaEntry) -> Option<&Path> {
+        let before = self.options.archive_before?;
+        let archive = self.options.archive_target.as_deref()?;
+        let date = entry.date.as_ref()?;
+        let before: Vec<String> = before
+            .format("%Y/%m/%d")
+            .to_string()
+            .split('/')
+            .map(String::from)
+            .collect();
+        (date_key(date) < before).then_some(archive)
+    }
+
+    fn find_all_media_files(
+        &mut self,
+        path: Option<&str>,
+        recursive: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = path.unwrap_or(&self.source).to_string();
+        let mut candidates = Vec::new();
+        self.collect_candidates(&path, recursive, &mut candidates)?;
+        self.scan_candidates(&candidates)
+    }
+
+    // Read the metadata of the collected files and add them to the found ones
+    fn scan_candidates(&mut self, candidates: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+        if let (Some(cache), None) = (&self.options.scan_cache, &self.scan_cache) {
+            self.scan_cache = Some(ScanCache::load(cache, &scan_cache_key(&self.options)));
+        }
+        // reading the metadata dominates on slow disks, so files are scanned
+        // concurrently and their results collected in the order they were found
+        let scan = || {
+            candidates
+                .par_iter()
+                .map(|path| self.scan_candidate(path))
+                .collect::<Vec<_>>()
+        };
+        let scanned = match self.options.scan_threads {
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()?
+                .install(scan),
+            None => scan(),
+        };
+        for (path, scanned) in candidates.iter().zip(scanned) {
+            if let Some((entry, cached)) = scanned {
+                let source = path.to_str().unwrap();
+                if let (Some(cache), Some(cached)) = (&mut self.scan_cache, cached) {
+                    cache.insert(source, cached);
+                }
+                self.files.insert(source.to_string(), entry);
+            }
+        }
+        if let (Some(path), Some(cache)) = (&self.options.scan_cache, &self.scan_cache) {
+            cache.save(path)?;
+        }
+        Ok(())
+    }
+
+    // Walk the folders and collect the media files to scan, in sorted order
+    fn collect_candidates(
+        &mut self,
+        path: &str,
+        recursive: bool,
+        candidates: &mut Vec<PathBuf>,
+    ) -> Result<(), Box<dyn Error>> {
+        // sorted, so the same one of several hardlinks is kept every run
+        let mut paths = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        for path in paths {
+            // files are tracked by their names as strings, so these cannot be sorted yet
+            let Some(name) = path.to_str() else {
+                warn!("Skipping {}, its name is not valid UTF-8", path.display());
+                continue;
+            };
+            if path.is_dir() {
+                if !recursive {
+                    continue;
+                }
+                if self.options.skip_empty_dirs && !contains_media(&path) {
+                    debug!("Skipping directory {}, no media files", path.display());
+                    continue;
+                }
+                self.collect_candidates(name, true, candidates)?;
+            } else if path.is_file() && is_media_file(&path) {
+                if contact_sheet::is_contact_sheet(&path) {
+                    continue;
+                }
+                if !self.options.download_placeholders && is_cloud_placeholder(&path) {
+                    info!(
+                        "Skipping {}, a cloud placeholder that is not downloaded",
+                        path.display()
+                    );
+                    continue;
+                }
+                if let Some(min_age) = self.options.min_age {
+                    if !is_settled(&path, min_age) {
+                        info!("Skipping {}, modified too recently", path.display());
+                        continue;
+                    }
+                }
+                if self.options.dedup_inodes {
+                    if let Some(identity) = file_identity(&path) {
+                        if !self.seen_files.insert(identity) {
+                            debug!(
+                                "Skipping {}, already found via another path",
+                                path.display()
+                            );
+                            continue;
+                        }
+                    }
+                }
+                candidates.push(path);
+            } else if self.options.strict_formats && path.is_file() && is_unsupported_media(&path) {
+                self.unsupported.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    // Read the metadata of a found file, None if it is left out. With a scan
+    // cache the date to remember for it is returned as well.
+    fn scan_candidate(&self, path: &Path) -> Option<(MediaEntry, Option<CachedDate>)> {
+        if let Some(min_resolution) = self.options.min_resolution {
+            if is_below_resolution(path, min_resolution, self.options.mmap_exif) {
+                info!(
+                    "Skipping {}, smaller than the minimum resolution",
+                    path.display()
+                );
+                return None;
+            }
+        }
+        if self.options.exposure_filter.is_set() {
+            let exposure = path
+                .to_str()
+                .and_then(|path| open_exif(path, self.options.mmap_exif));
+            let exposure = exposure.as_ref().map(exposure::read_exposure);
+            if !self
+                .options
+                .exposure_filter
+                .accepts(&exposure.unwrap_or_default())
+            {
+                info!("Skipping {}, outside the exposure filters", path.display());
+                return None;
+            }
+        }
+        if !self.options.skip_hashes.is_empty() {
+            match checksum::hex_digest(path, self.options.checksum_algo) {
+                Ok(hash) if self.options.skip_hashes.contains(&hash) => {
+                    info!("Skipping {}, its hash is listed", path.display());
+                    return None;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Could not hash {}: {}", path.display(), e),
+            }
+        }
+        if self.options.motion_photos && motion::is_motion_photo(path) {
+            info!(
+                "{} is a motion photo with an embedded video, keeping it as one file",
+                path.display()
+            );
+        }
+        let sourcepath = path.to_str().unwrap();
+        let scan = || match &self.scan_cache {
+            Some(cache) => cached_scan(sourcepath, &self.options, cache),
+            None => scan_file(sourcepath, &self.options).map(|entry| (entry, None)),
+        };
+        if self.options.catch_panics {
+            catch_panic(sourcepath, scan)
+        } else {
+            scan()
+        }
+    }
+}
+
+// Read the metadata of a file to find its target
+fn scan_file(sourcepath: &str, options: &Options) -> Option<MediaEntry> {
+    quarantine(sourcepath, options).or_else(|| target_path(sourcepath, options))
+}
+
+// Like scan_file, but reuse the date of an unchanged file from the scan cache
+fn cached_scan(
+    sourcepath: &str,
+    options: &Options,
+    cache: &ScanCache,
+) -> Option<(MediaEntry, Option<CachedDate>)> {
+    if let Some(entry) = quarantine(sourcepath, options) {
+        return Some((entry, None));
+    }
+    let stamp = scan_cache::stamp(Path::new(sourcepath));
+    let dated = match stamp.and_then(|stamp| cache.get(sourcepath, stamp)) {
+        Some(dated) => dated,
+        None => dated_path(sourcepath, options)?,
+    };
+    let cached = stamp.map(|stamp| CachedDate::new(stamp, &dated));
+    Some((place(sourcepath, dated, options), cached))
+}
+
+// The target of a file with unparsable exif data, with --quarantine-corrupt
+fn quarantine(sourcepath: &str, options: &Options) -> Option<MediaEntry> {
+    if !options.quarantine_corrupt {
+        return None;
+    }
+    let e = exif_parse_error(sourcepath)?;
+    warn!("Quarantining corrupt file {}: {}", sourcepath, e);
+    Some(MediaEntry {
+        target: format!("{}/{}", CORRUPT_FOLDER, file_name(sourcepath)),
+        date_source: None,
+        date: None,
+    })
+}
+
+// The settings that decide the date of a file; a scan cache written with
+// other ones is not reused
+fn scan_cache_key(options: &Options) -> String {
+    format!(
+        "{} {:?} {:?} {:?} {:?} {:?} {} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        options.strategy_order,
+        options.image_date,
+        options.video_date,
+        options.filename_date_ext,
+        options.camera_offsets,
+        options.oldest_date,
+        options.date_command,
+        options.day_cutoff
+    )
+}
+
+// Skip a file whose metadata parsing panics instead of aborting the run.
+// Unwinding is cheap while nothing panics, but it keeps the parsers from
+// being optimized as aggressively, so it is opt-in.
+fn catch_panic<T>(sourcepath: &str, scan: impl FnOnce() -> Option<T>) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(scan)) {
+        Ok(result) => result,
+        Err(_) => {
+            error!("Skipping {}, reading its metadata panicked", sourcepath);
+            None
+        }
+    }
+}
+
+/// The kinds of media files, which can be sorted into separate roots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MediaCategory {
+    Image,
+    Raw,
+    Video,
+    /// PDF scans, only with the pdf feature
+    Document,
+}
+
+const IMAGE_EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "png", "webp", "avif", "heic", "heif", "tiff"];
+const RAW_EXTENSIONS: [&str; 5] = ["dng", "nef", "cr2", "arw", "pef"];
+const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "mov", "mts", "m2ts"];
+const DOCUMENT_EXTENSIONS: [&str; 1] = ["pdf"];
+const UNSUPPORTED_EXTENSIONS: [&str; 15] = [
+    "gif", "bmp", "cr3", "raf", "orf", "rw2", "srw", "3gp", "avi", "m4v", "mkv", "webm", "wmv",
+    "mpg", "pdf",
+];
+
+fn media_category(path: &Path) -> Option<MediaCategory> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        Some(MediaCategory::Image)
+    } else if RAW_EXTENSIONS.contains(&ext.as_str()) {
+        Some(MediaCategory::Raw)
+    } else if VIDEO_EXTENSIONS.contains(&ext.as_str()) {
+        Some(MediaCategory::Video)
+    } else if DOCUMENT_EXTENSIONS.contains(&ext.as_str()) && pdf::is_supported() {
+        Some(MediaCategory::Document)
+    } else {
+        None
+    }
+}
+
+fn is_media_file(path: &Path) -> bool {
+    media_category(path).is_some()
+}
+
+// Media formats that are known but cannot be sorted, like PDFs without the pdf feature
+fn is_unsupported_media(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| UNSUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        && !is_media_file(path)
+}
+
+fn is_video_file(path: &Path) -> bool {
+    media_category(path) == Some(MediaCategory::Video)
+}
+
+// Parse a `key=dir` mapping, where key is a media category or a file extension
+fn parse_category_target(value: &str) -> Result<(String, PathBuf), String> {
+    let (key, root) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected <category|extension>=<dir>, got {}", value))?;
+    let key = key.trim().trim_start_matches('.').to_lowercase();
+    let known = MediaCategory::from_str(&key, true).is_ok()
+        || media_category(Path::new(&format!("file.{}", key))).is_some();
+    if !known {
+        return Err(format!(
+            "{} is neither a media category nor a media extension",
+            key
+        ));
+    }
+    Ok((key, PathBuf::from(root)))
+}
+
+// Parse a `CAMERA=[+-]HH:MM[:SS]` clock offset like E-M10MarkII=-01:00
+fn parse_camera_offset(value: &str) -> Result<(String, TimeDelta), String> {
+    let invalid = || format!("expected <camera>=[+-]HH:MM[:SS], got {}", value);
+    let (camera, offset) = value.rsplit_once('=').ok_or_else(invalid)?;
+    let (negative, offset) = match offset.trim().strip_prefix('-') {
+        Some(offset) => (true, offset),
+        None => (false, offset.trim().trim_start_matches('+')),
+    };
+    let parts = offset
+        .split(':')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let seconds = match parts[..] {
+        [hours, minutes] => hours * 3600 + minutes * 60,
+        [hours, minutes, seconds] => hours * 3600 + minutes * 60 + seconds,
+        _ => return Err(invalid()),
+    };
+    let camera = camera.trim();
+    if camera.is_empty() {
+        return Err(invalid());
+    }
+    let offset = TimeDelta::seconds(if negative { -seconds } else { seconds });
+    Ok((camera.to_string(), offset))
+}
+
+// Parse a --day-cutoff time of day like 04:00
+fn parse_day_cutoff(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("expected a time of day like 04:00, got {}", value))
+}
+
+// Parse an archive threshold, a date like 2024-01-01 or a number of months like 12m
+fn parse_archive_before(value: &str) -> Result<NaiveDate, String> {
+    let value = value.trim();
+    if let Some(months) = value.strip_suffix('m') {
+        let months = months
+            .parse::<u32>()
+            .map_err(|_| format!("expected a number of months like 12m, got {}", value))?;
+        return Local::now()
+            .date_naive()
+            .checked_sub_months(Months::new(months))
+            .ok_or_else(|| format!("{} months ago is out of range", months));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        format!(
+            "expected YYYY-MM-DD or a number of months like 12m, got {}",
+            value
+        )
+    })
+}
+
+// Parse a `WxH` resolution like 640x480
+fn parse_resolution(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected <width>x<height>, got {}", value);
+    let (width, height) = value
+        .to_lowercase()
+        .split_once('x')
+        .map(|(w, h)| (w.trim().parse::<u32>(), h.trim().parse::<u32>()))
+        .ok_or_else(invalid)?;
+    Ok((
+        width.map_err(|_| invalid())?,
+        height.map_err(|_| invalid())?,
+    ))
+}
+
+// Whether an image is narrower or lower than the minimum resolution. The
+// dimensions are read from the header only; videos and images whose header
+// cannot be read are never considered too small.
+fn is_below_resolution(path: &Path, (min_width, min_height): (u32, u32), mmap: bool) -> bool {
+    if is_video_file(path) {
+        return false;
+    }
+    displayed_dimensions(path, mmap)
+        .is_some_and(|(width, height)| width < min_width || height < min_height)
+}
+
+// The width and height of an image as displayed, read from its header
+fn displayed_dimensions(path: &Path, mmap: bool) -> Option<(u32, u32)> {
+    match image::image_dimensions(long_path(path)) {
+        Ok((width, height)) if is_rotated(path, mmap) => Some((height, width)),
+        Ok(dimensions) => Some(dimensions),
+        Err(e) => {
+            debug!("Could not read the dimensions of {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+// Whether the EXIF orientation turns the stored image by 90 degrees, so it is
+// displayed with width and height swapped
+fn is_rotated(path: &Path, mmap: bool) -> bool {
+    path.to_str()
+        .and_then(|path| open_exif(path, mmap))
+        .and_then(|exif| {
+            exif.get_field(Tag::Orientation, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .is_some_and(|orientation| (5..=8).contains(&orientation))
+}
+
+fn enough_space(root: &Path, needed: u64, available: u64) -> Result<(), Box<dyn Error>> {
+    if needed > available {
+        return Err(format!(
+            "not enough free space in {}: {} needed, {} available",
+            root.display(),
+            human_bytes(needed),
+            human_bytes(available)
+        )
+        .into());
+    }
+    debug!(
+        "{} needed in {}, {} available",
+        human_bytes(needed),
+        root.display(),
+        human_bytes(available)
+    );
+    Ok(())
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn file_size(path: &str) -> u64 {
+    fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+// Whether a file was last modified at least min_age ago
+fn is_settled(path: &Path, min_age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| modified.elapsed().unwrap_or_default() >= min_age)
+        .unwrap_or(false)
+}
+
+// Device and inode of a file, which are shared by all of its hardlinks
+#[cfg(unix)]
+fn file_identity(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+// Whether a file only is a placeholder of a cloud drive, whose content would
+// be downloaded on first access. Only its metadata is read, which does not.
+#[cfg(windows)]
+fn is_cloud_placeholder(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+    fs::metadata(path).is_ok_and(|metadata| {
+        metadata.file_attributes()
+            & (FILE_ATTRIBUTE_OFFLINE
+                | FILE_ATTRIBUTE_RECALL_ON_OPEN
+                | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+            != 0
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn is_cloud_placeholder(path: &Path) -> bool {
+    use std::os::macos::fs::MetadataExt;
+    const SF_DATALESS: u32 = 0x40000000;
+    fs::metadata(path).is_ok_and(|metadata| metadata.st_flags() & SF_DATALESS != 0)
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn is_cloud_placeholder(_path: &Path) -> bool {
+    false
+}
+
+// Peek into a directory tree, stopping at the first media file found
+fn contains_media(path: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(path) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let path = entry.path();
+        if path.is_dir() {
+            contains_media(&path)
+        } else {
+            path.is_file() && is_media_file(&path)
+        }
+    })
+}
+
+/// Environment variable overriding the default target directory
+pub const TARGET_ENV: &str = "MEDIASORT_TARGET";
+
+/// Environment variable forcing a dry run, whatever the command line says
+pub const DRY_RUN_ENV: &str = "MEDIASORT_DRY_RUN";
+
+// Whether the environment forces a dry run, any value but "0" or "false" does
+fn dry_run_forced() -> bool {
+    env::var(DRY_RUN_ENV)
+        .is_ok_and(|value| !matches!(value.trim().to_lowercase().as_str(), "" | "0" | "false"))
+}
+
+// Pick the target from the command line, the environment or the home directory
+fn resolve_target(target: Option<PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(target) = target {
+        return Ok(target);
+    }
+    if let Some(target) = env::var_os(TARGET_ENV).filter(|target| !target.is_empty()) {
+        return Ok(PathBuf::from(target));
+    }
+    let home = env::var("HOME")?;
+    Ok(Path::new(&home).join("Pictures"))
+}
+
+pub fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    if let Some(Command::Selftest) = args.command {
+        return selftest::run();
+    }
+    if args.low_priority {
+        // before any scan or copy thread is started, which inherit it
+        if !cfg!(target_os = "linux") {
+            warn!("--low-priority is only supported on Linux, ignoring it");
+        } else if let Err(e) = priority::lower() {
+            warn!("Could not lower the priority: {}", e);
+        }
+    }
+    let target = resolve_target(args.target)?;
+    let dry_run = if dry_run_forced() {
+        warn!(
+            "{} is set, forcing a dry run, no files will be copied",
+            DRY_RUN_ENV
+        );
+        true
+    } else {
+        args.dry_run
+    };
+    let staging = if args.from_camera {
+        Some(camera::download()?)
+    } else {
+        None
+    };
+    let mut folder = match &staging {
+        Some(staging) => staging.path().to_string_lossy().into_owned(),
+        None => args.folder.ok_or("--folder is required")?,
+    };
+    let mut source_label = staging.as_ref().map(|_| String::from("camera"));
+    let extracted = if archive::is_archive(Path::new(&folder)) {
+        Some(archive::extract_media(Path::new(&folder))?)
+    } else {
+        None
+    };
+    if let Some(extracted) = &extracted {
+        source_label = Some(folder);
+        folder = extracted.path().to_string_lossy().into_owned();
+    }
+    if !args.weekday_names.is_empty() && args.weekday_names.len() != 7 {
+        return Err("--weekday-names needs seven names, from Monday to Sunday".into());
+    }
+    let mut config = MediaConfig::new(folder, target);
+    config.options = Options {
+        recursive: args.recursive,
+        skip_empty_dirs: args.skip_empty_dirs,
+        dry_run,
+        report: args.report,
+        write_index: args.write_index,
+        dedupe_keep_original_location: args.dedupe_keep_original_location,
+        by_serial: args.by_serial,
+        by_lens: args.by_lens,
+        by_aspect: args.by_aspect,
+        aspect_videos: args.aspect_videos,
+        split_by_ext: args.split_by_ext,
+        by_person: args.by_person,
+        person_rule: args.person_rule,
+        retry_locked: args.retry_locked,
+        strategy_order: args.strategy_order,
+        verify_size: args.verify_size,
+        verify_all: args.verify_all,
+        dedup_inodes: args.dedup_inodes,
+        on_conflict: args.on_conflict,
+        collision_log: args.collision_log,
+        motion_photos: args.motion_photos,
+        summary_json: args.summary_json,
+        video_target: args.video_target,
+        screenshots_target: args.screenshots_target,
+        quarantine_corrupt: args.quarantine_corrupt,
+        strict_formats: args.strict_formats,
+        category_targets: args.category_target,
+        min_age: args.min_age.map(Duration::from_secs),
+        granularity: args.layout.map_or(args.granularity, Layout::granularity),
+        with_weekday: args.with_weekday,
+        weekday_names: args.weekday_names,
+        sequence_scope: args.sequence_scope,
+        layout: args.layout,
+        merge_into_existing_structure: args.merge_into_existing_structure,
+        preserve_xattr: args.preserve_xattr,
+        dedup_identical: args.dedup_identical,
+        move_files: args.move_files,
+        checksum_algo: args.checksum_algo,
+        filename_date_ext: args
+            .filename_date_ext
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect(),
+        catch_panics: args.catch_panics,
+        sidecars: args.sidecars || args.sync_sidecars,
+        sync_sidecars: args.sync_sidecars,
+        min_resolution: args.min_resolution,
+        symlink: args.symlink,
+        relative_links: args.relative_links,
+        resume_cursor: args.resume_cursor,
+        max_files_in_memory: args.max_files_in_memory.map(|max| max as usize),
+        copy_attributes: args.copy_attributes,
+        in_place_rename: args.in_place_rename,
+        oldest_date: args.oldest_date,
+        day_cutoff: args.day_cutoff,
+        contact_sheet: args.contact_sheet,
+        check_free_space: args.check_free_space,
+        wait_on_disk_full: args.wait_on_disk_full,
+        atomic: args.atomic,
+        camera_offsets: args.camera_offset,
+        scan_threads: args.scan_threads,
+        mmap_exif: args.mmap_exif,
+        scan_cache: args.scan_cache,
+        cas: args.cas,
+        image_date: args.image_date,
+        video_date: args.video_date,
+        download_placeholders: args.download_placeholders,
+        run_log: args.run_log,
+        exposure_filter: ExposureFilter {
+            min_iso: args.min_iso,
+            max_aperture: args.max_aperture,
+            min_focal_length: args.min_focal_length,
+        },
+        skip_hashes: match &args.skip_hashes {
+            Some(path) => checksum::read_hash_list(path)
+                .map_err(|e| format!("cannot read {}: {}", path.display(), e))?,
+            None => HashSet::new(),
+        },
+        temp_dir: args.temp_dir,
+        normalize_unicode: args.normalize_unicode,
+        write_exif_date: args.write_exif_date,
+        catalog: args.catalog,
+        progress_eta: args.progress_eta,
+        archive_before: args.archive_before,
+        archive_target: args.archive_target,
+        date_command: args.date_command,
+        date_command_timeout: Duration::from_secs(args.date_command_timeout),
+        source_label,
+    };
+    if extracted.is_some() && config.options.move_files {
+        warn!("Archives are only read, copying instead of moving");
+        config.options.move_files = false;
+    }
+    if config.options.source_label.is_some() && config.options.symlink {
+        // links would point into the temporary folder that is removed at the end
+        warn!("Archives and cameras are only staged temporarily, copying instead of linking");
+        config.options.symlink = false;
+    }
+    if let Some(path) = args.exif_csv {
+        return config.write_exif_csv(&path);
+    }
+    if let Some(reference) = args.compare_with_reference {
+        print!("{}", config.compare_with_reference(&reference)?);
+        return Ok(());
+    }
+    if args.report_unknown_ext {
+        let counts = unknown_ext::count(Path::new(&config.source), config.options.recursive)?;
+        print!("{}", unknown_ext::report(&counts));
+        return Ok(());
+    }
+    if let Some(sample) = args.suggest_config {
+        print!("{}", config.suggest_config(sample)?);
+        return Ok(());
+    }
+    let stop = config.stop_handle();
+    // the first Ctrl+C finishes the current file, a second one still interrupts
+    ctrlc::set_handler(move || {
+        if stop.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    })?;
+    let summary = config.copy_media_files()?;
+    if config.options.summary_json {
+        println!("{}", serde_json::to_string(&summary)?);
+    }
+    if let Some(mismatches @ 1..) = summary.verify_mismatches {
+        return Err(format!("{} copies do not match their sources", mismatches).into());
+    }
+    Ok(())
+}
+
+// Record a file of a staged source under the archive or camera it came from
+// instead of the temporary folder that is gone after the run
+fn staged_label(source: &str, staged: Option<&(String, String)>) -> String {
+    match staged.and_then(|(folder, label)| Some((source.strip_prefix(folder.as_str())?, label))) {
+        Some((rest, label)) => format!("{}{}", label, rest),
+        None => source.to_string(),
+    }
+}
+
+// How long to wait before retrying files that were locked by another process
+const RETRY_LOCKED_DELAY: Duration = Duration::from_secs(2);
+
+// Whether a copy failed because another process (antivirus, indexer) holds the file
+fn is_locked(error: &(dyn Error + 'static)) -> bool {
+    let Some(error) = error.downcast_ref::<io::Error>() else {
+        return false;
+    };
+    if cfg!(windows) {
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+        matches!(error.raw_os_error(), Some(32) | Some(33))
+    } else {
+        error.kind() == io::ErrorKind::ResourceBusy
+    }
+}
+
+// Whether a copy failed because the target filesystem is full
+fn is_disk_full(error: &(dyn Error + 'static)) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|error| error.kind() == io::ErrorKind::StorageFull)
+}
+
+// Ask to free some space and wait for Enter. False when stdin is closed,
+// so unattended runs stop instead of waiting forever.
+fn wait_for_space(target: &str) -> io::Result<bool> {
+    eprintln!(
+        "The target is full while copying {}. Free some space and press Enter to continue, or Ctrl+C to stop.",
+        target
+    );
+    let mut line = String::new();
+    Ok(io::stdin().read_line(&mut line)? > 0)
+}
+
+// Copy file from one directory to another
+fn copy_file(from: &str, to: &str, options: &Options) -> Result<bool, Box<dyn Error>> {
+    let abs_path = Path::new(&to);
+    let parent = abs_path.parent().unwrap();
+    create_dir(parent.to_str().unwrap())?;
+    if long_path(abs_path).exists() {
+        if options.on_conflict == ConflictPolicy::Overwrite
+            && same_content(from, to, options.checksum_algo)
+        {
+            info!("Skipping File {}, identical to {}", from, to);
+            return Ok(false);
+        }
+        if !options
+            .on_conflict
+            .overwrites(&long_path(Path::new(from)), &long_path(abs_path))
+        {
+            warn!("Skipping File {}, already exists", to);
+            return Ok(false);
+        }
+        info!("Overwriting existing file {}", to);
+    }
+    if options.symlink {
+        info!("Link file {} to {}", abs_path.display(), from);
+        link::link_file(Path::new(from), abs_path, options.relative_links)?;
+        return Ok(true);
+    }
+    if let Some(store) = &options.cas {
+        store_file(from, abs_path, store, options)?;
+        return Ok(true);
+    }
+    info!("Copy file {} to {}", from, abs_path.display());
+    copy_contents(&long_path(Path::new(from)), &long_path(abs_path), options)?;
+    if options.verify_size && verify_size(from, to).is_err() {
+        warn!("Retrying truncated copy of {}", from);
+        copy_contents(&long_path(Path::new(from)), &long_path(abs_path), options)?;
+        verify_size(from, to)?;
+    }
+    if options.move_files {
+        verify_moved_copy(from, abs_path, options)?;
+    }
+    if let Some(time) = exif_date_to_write(from, options) {
+        // only the copy is changed, after it was verified against its source
+        // and while the source is still there
+        match exif_date::write_date_time_original(&long_path(abs_path), time) {
+            Ok(true) => info!("Wrote EXIF date {} into {}", time, to),
+            Ok(false) => {}
+            Err(e) => warn!("Could not write the EXIF date into {}: {}", to, e),
+        }
+    }
+    if options.preserve_xattr {
+        copy_xattrs(Path::new(from), abs_path)?;
+    }
+    if options.copy_attributes {
+        copy_attributes(Path::new(from), abs_path)?;
+    }
+    if options.move_files {
+        info!("Removing verified source {}", from);
+        fs::remove_file(long_path(Path::new(from)))?;
+    }
+    Ok(true)
+}
+
+// With --write-exif-date the date to write into the copy of a file. Links
+// and --cas copies are left alone.
+fn exif_date_to_write(from: &str, options: &Options) -> Option<NaiveDateTime> {
+    if !options.write_exif_date || options.symlink || options.cas.is_some() {
+        return None;
+    }
+    missing_exif_time(from, options)
+}
+
+// The time in the name of a JPEG without exif data, to write into its copy
+fn missing_exif_time(filename: &str, options: &Options) -> Option<NaiveDateTime> {
+    let extension = Path::new(filename).extension()?.to_str()?.to_lowercase();
+    if !["jpg", "jpeg"].contains(&extension.as_str())
+        || !trusts_filename_date(filename, options)
+        || open_exif(filename, options.mmap_exif).is_some()
+    {
+        return None;
+    }
+    rename::filename_time(filename)
+}
+
+// Copy a file into the content-addressed store unless its content is stored
+// already, and link its target to the stored object
+fn store_file(
+    from: &str,
+    to: &Path,
+    store: &Path,
+    options: &Options,
+) -> Result<(), Box<dyn Error>> {
+    let source = long_path(Path::new(from));
+    let object = cas::object_path(store, &source, options.checksum_algo)?;
+    if long_path(&object).exists() {
+        info!(
+            "Content of {} is already stored as {}",
+            from,
+            object.display()
+        );
+    } else {
+        info!("Store file {} as {}", from, object.display());
+        create_dir(object.parent().unwrap().to_str().unwrap())?;
+        copy_contents(&source, &long_path(&object), options)?;
+    }
+    info!("Link file {} to {}", to.display(), object.display());
+    link::link_file(&object, to, options.relative_links)?;
+    if options.move_files {
+        remove_verified_source(from, &object, options)?;
+    }
+    Ok(())
+}
+
+// Copy the contents of a file. Atomic copies are written to a .tmp file,
+// next to the target or in --temp-dir, that is renamed into place once
+// complete, or removed if the copy fails.
+fn copy_contents(from: &Path, to: &Path, options: &Options) -> io::Result<u64> {
+    if !options.atomic {
+        return fs::copy(from, to);
+    }
+    let temp = temp_path(to, options.temp_dir.as_deref());
+    let copied = fs::copy(from, &temp).and_then(|bytes| {
+        match fs::rename(&temp, to) {
+            // a --temp-dir on another filesystem, warned about at the start
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                fs::copy(&temp, to)?;
+                fs::remove_file(&temp)?;
+            }
+            renamed => renamed?,
+        }
+        Ok(bytes)
+    });
+    if copied.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    copied
+}
+
+fn temp_path(target: &Path, temp_dir: Option<&Path>) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_owned();
+    name.push(".tmp");
+    match temp_dir {
+        Some(temp_dir) => temp_dir.join(name),
+        None => target.with_file_name(name),
+    }
+}
+
+// Whether two paths are on the same filesystem, None where that is unknown
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let device = |path: &Path| {
+        // the target may not exist yet, ask the closest folder that does
+        let existing = path.ancestors().find(|folder| folder.exists())?;
+        fs::metadata(existing).ok().map(|metadata| metadata.dev())
+    };
+    Some(device(a)? == device(b)?)
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> Option<bool> {
+    None
+}
+
+// Check that the copy of a move has the same checksum as its source before
+// the source is deleted. A copy that does not match is made once more, the
+// source is kept if that fails as well.
+fn verify_moved_copy(from: &str, to: &Path, options: &Options) -> Result<(), Box<dyn Error>> {
+    let source = long_path(Path::new(from));
+    let target = long_path(to);
+    if checksum::verify_copy(&source, &target, options.checksum_algo).is_err() {
+        warn!("Retrying corrupt copy of {}", from);
+        copy_contents(&source, &target, options)?;
+        checksum::verify_copy(&source, &target, options.checksum_algo)?;
+    }
+    Ok(())
+}
+
+// Delete the source of a move once its copy has the same checksum
+fn remove_verified_source(from: &str, to: &Path, options: &Options) -> Result<(), Box<dyn Error>> {
+    verify_moved_copy(from, to, options)?;
+    info!("Removing verified source {}", from);
+    fs::remove_file(long_path(Path::new(from)))?;
+    Ok(())
+}
+
+// Copy the extended attributes, warning about single ones the target refuses
+// (e.g. protected com.apple.* or security.* attributes)
+#[cfg(unix)]
+fn copy_xattrs(from: &Path, to: &Path) -> io::Result<()> {
+    for name in xattr::list(from)? {
+        let Some(value) = xattr::get(from, &name)? else {
+            continue;
+        };
+        if let Err(e) = xattr::set(to, &name, &value) {
+            warn!(
+                "Could not copy attribute {} to {}: {}",
+                name.to_string_lossy(),
+                to.display(),
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+// Give the copy the owner, group and mode of its source. Changing the owner
+// usually needs root, without the permission the copy is kept as it is.
+#[cfg(unix)]
+fn copy_attributes(from: &Path, to: &Path) -> io::Result<()> {
+    use nix::unistd::{chown, Gid, Uid};
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    let metadata = fs::metadata(from)?;
+    if let Err(e) = chown(
+        to,
+        Some(Uid::from_raw(metadata.uid())),
+        Some(Gid::from_raw(metadata.gid())),
+    ) {
+        warn!("Could not change the owner of {}: {}", to.display(), e);
+    }
+    fs::set_permissions(to, fs::Permissions::from_mode(metadata.mode()))
+}
+
+#[cfg(not(unix))]
+fn copy_attributes(_from: &Path, _to: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_from: &Path, _to: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// The files of a folder prefixed with a sequence number, like 0001_IMG_1.jpg
+fn numbered_files(folder: &Path) -> Vec<(u32, PathBuf)> {
+    lazy_static! {
+        static ref NUMBERED: Regex = Regex::new(r"^(\d{4,9})_.").unwrap();
+    }
+    let Ok(entries) = fs::read_dir(long_path(folder)) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let number = NUMBERED.captures(name.to_str()?)?[1].parse().ok()?;
+            Some((number, folder.join(name)))
+        })
+        .collect()
+}
+
+// Whether two files have the same content, comparing the sizes first so
+// that only files of equal size are hashed
+fn same_content(from: &str, to: &str, algo: ChecksumAlgo) -> bool {
+    let (from, to) = (long_path(Path::new(from)), long_path(Path::new(to)));
+    match (fs::metadata(&from), fs::metadata(&to)) {
+        (Ok(from), Ok(to)) if from.len() == to.len() => {}
+        _ => return false,
+    }
+    match (
+        checksum::file_digest(&from, algo),
+        checksum::file_digest(&to, algo),
+    ) {
+        (Ok(from), Ok(to)) => from == to,
+        _ => false,
+    }
+}
+
+// The existing target, or a copy an earlier run renamed next to it, with the
+// same content as the source, which is then skipped instead of renamed again
+fn identical_copy(source: &str, target: &Path, algo: ChecksumAlgo) -> Option<PathBuf> {
+    std::iter::once(target.to_path_buf())
+        .chain(conflict::renamed_siblings(target))
+        .find(|existing| {
+            existing
+                .to_str()
+                .is_some_and(|existing| same_content(source, existing, algo))
+        })
+}
+
+// Whether the source already is the target file, copying it onto itself
+fn is_same_file(source: &str, target: &Path) -> bool {
+    match (
+        fs::canonicalize(long_path(Path::new(source))),
+        fs::canonicalize(long_path(target)),
+    ) {
+        (Ok(source), Ok(target)) => source == target,
+        _ => false,
+    }
+}
+
+// Check the copy has the size of its source, removing it otherwise
+fn verify_size(from: &str, to: &str) -> Result<(), Box<dyn Error>> {
+    let expected = fs::metadata(long_path(Path::new(from)))?.len();
+    let actual = fs::metadata(long_path(Path::new(to)))?.len();
+    if expected != actual {
+        error!(
+            "Size mismatch for {}: expected {} bytes, got {}",
+            to, expected, actual
+        );
+        fs::remove_file(long_path(Path::new(to)))?;
+        return Err(format!("copy of {} is truncated", from).into());
+    }
+    Ok(())
+}
+
+// Create directory, if it does not exist
+fn create_dir(path: &str) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(long_path(Path::new(path)))?;
+    Ok(())
+}
+
+// Windows rejects paths longer than MAX_PATH (260) unless they carry the
+// extended-length prefix `\\?\`, which only works on absolute paths.
+// Directories are limited to 248 characters, so prefix from there on.
+#[cfg(windows)]
+fn long_path(path: &Path) -> PathBuf {
+    const MAX_DIR_PATH: usize = 248;
+    let raw = path.as_os_str().to_string_lossy();
+    if raw.len() < MAX_DIR_PATH || raw.starts_with(r"\\?\") {
+        return path.to_path_buf();
+    }
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let absolute = absolute.to_string_lossy().into_owned();
+    match absolute.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => PathBuf::from(format!(r"\\?\{}", absolute)),
+    }
+}
+
+#[cfg(not(windows))]
+fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+// Determine the target path by trying each date strategy in turn
+fn target_path(filename: &str, options: &Options) -> Option<MediaEntry> {
+    let entry = dated_path(filename, options)?;
+    Some(place(filename, entry, options))
+}
+
+// Turn the date path of a file into its target, applying the folder options
+fn place(filename: &str, mut entry: MediaEntry, options: &Options) -> MediaEntry {
+    // taken before the granularity drops the day
+    let weekday = if options.with_weekday {
+        weekday_folder(&entry.target, &options.weekday_names)
+    } else {
+        None
+    };
+    entry.target = layout::apply_granularity(&entry.target, options.granularity);
+    if let Some(layout) = options.layout {
+        entry.target = layout.format(&entry.target);
+    }
+    if let Some(weekday) = weekday {
+        entry.target = insert_folder(&entry.target, &weekday);
+    }
+    if options.by_serial {
+        let serial = exif_folder(
+            filename,
+            Tag::BodySerialNumber,
+            "unknown-serial",
+            options.mmap_exif,
+        );
+        entry.target = insert_folder(&entry.target, &serial);
+    }
+    if options.by_lens {
+        let lens = exif_folder(filename, Tag::LensModel, "unknown-lens", options.mmap_exif);
+        entry.target = insert_folder(&entry.target, &lens);
+    }
+    if options.by_aspect {
+        if let Some(aspect) = aspect_folder(filename, options) {
+            entry.target = insert_folder(&entry.target, aspect);
+        }
+    }
+    if options.split_by_ext {
+        if let Some(ext) = Path::new(filename).extension() {
+            let ext = ext.to_string_lossy().to_lowercase();
+            entry.target = insert_folder(&entry.target, &ext);
+        }
+    }
+    if options.by_person {
+        if let Some(person) = person_folder(filename, options.person_rule) {
+            entry.target = format!("people/{}/{}", person, entry.target);
+        }
+    }
+    if options.normalize_unicode {
+        // macOS tends to write decomposed (NFD) names, most others composed ones
+        entry.target = entry.target.nfc().collect();
+    }
+    entry
+}
+
+// The folder for the people tagged in a photo, None without named faces
+fn person_folder(filename: &str, rule: PersonRule) -> Option<String> {
+    let mut names: Vec<String> = xmp::read_face_names(Path::new(filename))
+        .iter()
+        .filter_map(|name| sanitize_component(name))
+        .collect();
+    match rule {
+        PersonRule::First => names.into_iter().next(),
+        PersonRule::Alphabetical => names.into_iter().min(),
+        PersonRule::All => {
+            names.sort();
+            names.dedup();
+            (!names.is_empty()).then(|| names.join("+"))
+        }
+    }
+}
+
+// The orientation folder of a photo, or of a video with --aspect-videos.
+// Files whose dimensions cannot be read stay where they are.
+fn aspect_folder(filename: &str, options: &Options) -> Option<&'static str> {
+    let path = Path::new(filename);
+    let (width, height) = if is_video_file(path) {
+        if !options.aspect_videos {
+            return None;
+        }
+        video::read_video_dimensions(filename)?
+    } else {
+        displayed_dimensions(path, options.mmap_exif)?
+    };
+    Some(match width.cmp(&height) {
+        std::cmp::Ordering::Less => "portrait",
+        std::cmp::Ordering::Equal => "square",
+        std::cmp::Ordering::Greater => "landscape",
+    })
+}
+
+// A folder named after an ASCII exif field, or the fallback without one
+fn exif_folder(filename: &str, tag: Tag, fallback: &str, mmap: bool) -> String {
+    open_exif(filename, mmap)
+        .and_then(|exif| ascii_field(&exif, tag))
+        .and_then(|value| sanitize_component(&value))
+        .unwrap_or_else(|| String::from(fallback))
+}
+
+// The date path of the first strategy that matches, noting where the date came from
+fn dated_path(filename: &str, options: &Options) -> Option<MediaEntry> {
+    let order = strategy_order(filename, options);
+    // --date-command stands in front of mtime, or comes last without it
+    let command_last = !order.contains(&Strategy::Mtime);
+    let mut candidates = order
+        .iter()
+        .filter(|strategy| {
+            **strategy != Strategy::Filename || trusts_filename_date(filename, options)
+        })
+        .filter_map(|strategy| {
+            if *strategy == Strategy::Mtime {
+                if let Some(entry) = command_date(filename, options) {
+                    return Some(entry);
+                }
+            }
+            let shifted = match strategy {
+                Strategy::Exif if !options.camera_offsets.is_empty() => {
+                    read_shifted_exif(filename, options)
+                }
+                _ => None,
+            };
+            shifted
+                .or_else(|| strategy.target_path(filename, options))
+                .map(|target| {
+                    MediaEntry::dated(
+                        before_day_cutoff(filename, *strategy, target, options),
+                        strategy.date_source(),
+                    )
+                })
+        })
+        .chain(
+            std::iter::once_with(|| command_last.then(|| command_date(filename, options)))
+                .flatten()
+                .flatten(),
+        );
+    if !options.oldest_date {
+        return candidates.next();
+    }
+    let candidates: Vec<MediaEntry> = candidates.collect();
+    debug!(
+        "Dates of {}: {}",
+        filename,
+        candidates
+            .iter()
+            .map(|entry| format!("{} ({})", date_folders(&entry.target), entry.origin()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    candidates
+        .into_iter()
+        .min_by_key(|entry| date_key(&entry.target))
+}
+
+// Move a target found by a strategy that knows the capture time to the
+// previous day, if it was taken after midnight but before --day-cutoff
+fn before_day_cutoff(
+    filename: &str,
+    strategy: Strategy,
+    target: String,
+    options: &Options,
+) -> String {
+    let Some(cutoff) = options.day_cutoff else {
+        return target;
+    };
+    let Some(taken) = rename::strategy_time(filename, strategy, options.mmap_exif) else {
+        return target;
+    };
+    let folders = date_folders(&target);
+    if taken.time() >= cutoff || folders != taken.format("%Y/%m/%d").to_string() {
+        return target;
+    }
+    match taken.date().pred_opt() {
+        Some(previous) => {
+            debug!(
+                "{} was taken before the day cutoff, dating it a day earlier",
+                filename
+            );
+            format!(
+                "{}{}",
+                previous.format("%Y/%m/%d"),
+                &target[folders.len()..]
+            )
+        }
+        None => target,
+    }
+}
+
+// The date path from --date-command, which stands in front of the mtime fallback
+fn command_date(filename: &str, options: &Options) -> Option<MediaEntry> {
+    let command = options.date_command.as_deref()?;
+    let date = date_command::resolve(command, Path::new(filename), options.date_command_timeout)?;
+    Some(MediaEntry::dated(
+        dated_target(&date.format("%Y/%m/%d").to_string(), filename),
+        DateSource::DateCommand,
+    ))
+}
+
+// The strategy order for a file, images and videos may have their own
+fn strategy_order<'a>(filename: &str, options: &'a Options) -> &'a [Strategy] {
+    let order = match media_category(Path::new(filename)) {
+        Some(MediaCategory::Video) => &options.video_date,
+        Some(MediaCategory::Image | MediaCategory::Raw) => &options.image_date,
+        Some(MediaCategory::Document) | None => &options.strategy_order,
+    };
+    if order.is_empty() {
+        &options.strategy_order
+    } else {
+        order
+    }
+}
+
+// Sort key of the date folders of a target. A year or month alone counts as
+// its last day, so a full date within it is considered older.
+fn date_key(target: &str) -> Vec<String> {
+    let mut key: Vec<String> = date_folders(target).split('/').map(String::from).collect();
+    key.resize(3, String::from("99"));
+    key
+}
+
+// Whether the date in the name of a file may be used for its extension
+fn trusts_filename_date(filename: &str, options: &Options) -> bool {
+    if options.filename_date_ext.is_empty() {
+        return true;
+    }
+    Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| options.filename_date_ext.contains(&ext.to_lowercase()))
+}
+
+// The date folders of a relative target, without any extra folders or the filename
+fn date_folders(target: &str) -> String {
+    target
+        .split('/')
+        .take_while(|part| part.chars().all(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+// The name of the weekday of a target with a full date, from the given names
+// starting on Monday or in English
+fn weekday_folder(target: &str, names: &[String]) -> Option<String> {
+    let date = NaiveDate::parse_from_str(&date_folders(target), "%Y/%m/%d").ok()?;
+    let day = date.weekday().num_days_from_monday() as usize;
+    Some(
+        names
+            .get(day)
+            .cloned()
+            .unwrap_or_else(|| WEEKDAYS[day].to_string()),
+    )
+}
+
+// Insert an additional folder between the date folders and the filename
+fn insert_folder(target: &str, folder: &str) -> String {
+    match target.rsplit_once('/') {
+        Some((dates, name)) => format!("{}/{}/{}", dates, folder, name),
+        None => format!("{}/{}", folder, target),
+    }
+}
+
+// Make an arbitrary metadata string usable as a single path component
+fn sanitize_component(value: &str) -> Option<String> {
+    let sanitized: String = value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let sanitized = sanitized.trim_matches(|c| c == '_' || c == '.');
+    if sanitized.is_empty() {
+        None
+    } else {
+        Some(sanitized.to_string())
+    }
+}
+
+// Read date from smartphone image or video filename
+fn smartphone_file(filename: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r"(?x)
+  (?:IMG|VID)_
+  (?P<y>\d{4}) # the year
+  (?P<m>\d{2}) # the month
+  (?P<d>\d{2}) # the day
+  _(\d{6}).(?:jpg|mp4)
+"
+        )
+        .unwrap();
+    };
+
+    RE.captures(filename)
+        .map(|cap| {
+            dated_target(
+                &format!("{}/{}/{}", &cap["y"], &cap["m"], &cap["d"]),
+                filename,
+            )
+        })
+        .or_else(|| epoch_millis_file(filename))
+        .or_else(|| screenshot_file(filename))
+        .or_else(|| messenger_file(filename))
+}
+
+// Read the date from the names messaging apps give saved media, like
+// `signal-2023-01-15-102911.jpg` from Signal, `photo_2023-01-15_10-29-11.jpg`
+// from Telegram Desktop and `IMG-20230115-WA0001.jpg` or `VID-20230115-WA0001.mp4`
+// from WhatsApp
+fn messenger_file(filename: &str) -> Option<String> {
+    lazy_static! {
+        static ref PATTERNS: [Regex; 3] = [
+            Regex::new(r"^signal-(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})-\d{2}-?\d{2}-?\d{2}")
+                .unwrap(),
+            Regex::new(
+                r"^(?:photo|video)_(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})_\d{2}-\d{2}-\d{2}"
+            )
+            .unwrap(),
+            Regex::new(r"^(?:IMG|VID|AUD|PTT)-(?P<y>\d{4})(?P<m>\d{2})(?P<d>\d{2})-WA\d+").unwrap(),
+        ];
+    };
+    let cap = PATTERNS
+        .iter()
+        .find_map(|re| re.captures(file_name(filename)))?;
+    NaiveDate::from_ymd_opt(
+        cap["y"].parse().ok()?,
+        cap["m"].parse().ok()?,
+        cap["d"].parse().ok()?,
+    )?;
+    Some(dated_target(
+        &format!("{}/{}/{}", &cap["y"], &cap["m"], &cap["d"]),
+        filename,
+    ))
+}
+
+// Read the date from screenshot names, like `Screenshot 2023-01-15 at 10.29.11.png`
+// and `Screen Shot 2019-05-01 at ...` on macOS or `Screenshot 2023-01-15 102911.png`
+// from the Windows Snipping Tool. The plain `Screenshot (12).png` of Windows has no
+// date and is left to the other strategies.
+fn screenshot_file(filename: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r"^(?i:screen ?shot)[ _-](?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})(?:[ _]|\.[a-zA-Z]+$)"
+        )
+        .unwrap();
+    };
+    let cap = RE.captures(file_name(filename))?;
+    NaiveDate::from_ymd_opt(
+        cap["y"].parse().ok()?,
+        cap["m"].parse().ok()?,
+        cap["d"].parse().ok()?,
+    )?;
+    Some(dated_target(
+        &format!("{}/{}/{}", &cap["y"], &cap["m"], &cap["d"]),
+        filename,
+    ))
+}
+
+// Whether a file looks like a screenshot: named like one, including the
+// undated names of Windows and the Screenshot_20230115-102911.png of Android,
+// or a PNG without the camera make and model that photos carry
+fn is_screenshot(filename: &str, mmap: bool) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?i:screen ?shot)").unwrap();
+    };
+    if RE.is_match(file_name(filename)) {
+        return true;
+    }
+    filename.to_lowercase().ends_with(".png")
+        && open_exif(filename, mmap).is_none_or(|exif| {
+            ascii_field(&exif, Tag::Make).is_none() && ascii_field(&exif, Tag::Model).is_none()
+        })
+}
+
+// Read the date from names like content_1673778551000.jpg, which some Android
+// apps build from the epoch in milliseconds. Other numbers in names, like the
+// MediaStore ID in 1000012345.jpg, are no dates: only 13 digit numbers that
+// fall between 2000 and now are trusted.
+fn epoch_millis_file(filename: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?:[A-Za-z]+_)?(?P<ms>\d{13})$").unwrap();
+    };
+    let stem = Path::new(filename).file_stem()?.to_str()?;
+    let millis: i64 = RE.captures(stem)?["ms"].parse().ok()?;
+    let datetime = DateTime::from_timestamp_millis(millis)?.with_timezone(&Local);
+    if datetime.year() < 2000 || datetime > Local::now() {
+        return None;
+    }
+    Some(dated_target(
+        &datetime.format("%Y/%m/%d").to_string(),
+        filename,
+    ))
+}
+
+// Containers the exif crate can extract a date from
+fn has_exif_container(filename: &str) -> bool {
+    let filename = filename.to_lowercase();
+    [
+        ".jpg", ".jpeg", ".png", ".webp", ".avif", ".heic", ".heif", ".tiff", ".dng", ".nef",
+        ".cr2", ".arw", ".pef",
+    ]
+    .iter()
+    .any(|ext| filename.ends_with(ext))
+}
+
+// The exif dates in the order they are trusted, the capture date first. Files
+// that were only digitized or edited later still carry the others.
+const EXIF_DATE_TAGS: [Tag; 3] = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime];
+
+// Only the primary image (IFD0 and its EXIF IFD) counts. The thumbnail IFD
+// may carry a date of its own, e.g. from an earlier edit, which is ignored.
+fn read_exif(filename: &str, mmap: bool) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})\s+(?:\d|:){8}").unwrap();
+    };
+    let date = open_exif(filename, mmap)
+        .and_then(|exif| {
+            find_exif_date(&exif, filename, |value| {
+                RE.captures(value)
+                    .map(|cap| format!("{}/{}/{}", &cap["y"], &cap["m"], &cap["d"]))
+            })
+        })
+        .or_else(|| {
+            // DNG converters often only write the date into the XMP packet
+            if filename.to_lowercase().ends_with(".dng") {
+                xmp::read_embedded_date(Path::new(filename))
+            } else {
+                None
+            }
+        })
+        .or_else(|| xmp::read_sidecar_date(Path::new(filename)))?;
+    Some(dated_target(&date, filename))
+}
+
+// The first of the EXIF_DATE_TAGS of the primary image that parses, given
+// its displayed value ("yyyy-mm-dd HH:MM:SS")
+fn find_exif_date<T>(
+    exif: &exif::Exif,
+    filename: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Option<T> {
+    EXIF_DATE_TAGS.iter().find_map(|tag| {
+        let field = exif.get_field(*tag, In::PRIMARY)?;
+        let date = parse(&field.display_value().to_string())?;
+        if *tag != Tag::DateTimeOriginal {
+            debug!("No DateTimeOriginal in {}, using {}", filename, tag);
+        }
+        Some(date)
+    })
+}
+
+// The capture time from the EXIF_DATE_TAGS of a file
+fn exif_capture_time(filename: &str, mmap: bool) -> Option<NaiveDateTime> {
+    let exif = open_exif(filename, mmap)?;
+    find_exif_date(&exif, filename, parse_exif_time)
+}
+
+fn parse_exif_time(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok()
+}
+
+// The EXIF date of a camera with a clock offset, shifted by it. None for
+// other cameras, which are dated by the plain EXIF strategy.
+fn read_shifted_exif(filename: &str, options: &Options) -> Option<String> {
+    let exif = open_exif(filename, options.mmap_exif)?;
+    let cameras = [Tag::Model, Tag::BodySerialNumber].map(|tag| ascii_field(&exif, tag));
+    let (camera, offset) = options
+        .camera_offsets
+        .iter()
+        .find(|(camera, _)| cameras.iter().flatten().any(|name| name == camera))?;
+    let taken = find_exif_date(&exif, filename, parse_exif_time)?;
+    let shifted = taken.checked_add_signed(*offset)?;
+    debug!(
+        "Shifted {} of {} by {} to {}",
+        taken, camera, offset, shifted
+    );
+    Some(dated_target(
+        &shifted.format("%Y/%m/%d").to_string(),
+        filename,
+    ))
+}
+
+fn read_video_date(filename: &str) -> Option<String> {
+    if !is_video_file(Path::new(filename)) {
+        return None;
+    }
+    let lower = filename.to_lowercase();
+    let date = if lower.ends_with(".mts") || lower.ends_with(".m2ts") {
+        avchd::read_avchd_date(filename)
+    } else {
+        video::read_video_date(filename)
+    };
+    date.map(|date| dated_target(&date, filename))
+}
+
+// Read the date of a PDF scan from its document information
+fn read_pdf_date(filename: &str) -> Option<String> {
+    if media_category(Path::new(filename)) != Some(MediaCategory::Document) {
+        return None;
+    }
+    pdf::read_pdf_date(filename)
+        .map(|date| dated_target(&date.format("%Y/%m/%d").to_string(), filename))
+}
+
+// Read a year, year-month or full date from the name of the parent directory
+fn read_directory_date(filename: &str) -> Option<String> {
+    Some(dated_target(&directory_date(filename)?, filename))
+}
+
+// The year, year-month or full date folders named by the parent directory
+fn directory_date(filename: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r"(?x)
+  (?:^|\D)
+  (?P<y>(?:19|20)\d{2})                        # the year
+  (?:[-_](?P<m>0[1-9]|1[0-2])                  # optionally the month
+     (?:[-_](?P<d>0[1-9]|[12]\d|3[01]))?)?     # and the day
+  (?:\D|$)
+"
+        )
+        .unwrap();
+    };
+    let directory = Path::new(filename).parent()?.file_name()?.to_str()?;
+    let cap = RE.captures(directory)?;
+    Some(
+        [cap.name("y"), cap.name("m"), cap.name("d")]
+            .iter()
+            .flatten()
+            .map(|part| part.as_str())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+// Date a file named by a bare sequence number, like 001.jpg, by its folder
+// if that names a full date
+fn read_burst_date(filename: &str) -> Option<String> {
+    Some(dated_target(&burst_date(filename)?, filename))
+}
+
+// The full date folders of a shot in a burst folder, like 2023-01-15 Burst/001.jpg
+fn burst_date(filename: &str) -> Option<String> {
+    lazy_static! {
+        static ref SEQUENCE: Regex = Regex::new(r"^\d{1,6}$").unwrap();
+    }
+    let sequence = Path::new(filename).file_stem()?.to_str()?;
+    if !SEQUENCE.is_match(sequence) {
+        return None;
+    }
+    let folders = directory_date(filename)?;
+    (folders.matches('/').count() == 2).then_some(folders)
+}
+
+// The relative target of a file below its date folders. Every strategy names
+// its targets here, so the name does not depend on which one found the date.
+fn dated_target(folders: &str, filename: &str) -> String {
+    format!("{}/{}", folders, target_name(filename))
+}
+
+// The name of a file in the target. Shots of a burst are named after their
+// folder and number, so the shots of several bursts on one day do not collide.
+fn target_name(filename: &str) -> String {
+    burst_name(filename).unwrap_or_else(|| file_name(filename).to_string())
+}
+
+fn burst_name(filename: &str) -> Option<String> {
+    burst_date(filename)?;
+    let path = Path::new(filename);
+    let burst = sanitize_component(path.parent()?.file_name()?.to_str()?)?;
+    let mut name = format!("{}_{}", burst, path.file_stem()?.to_str()?);
+    if let Some(ext) = path.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy());
+    }
+    Some(name)
+}
+
+// Read the exif data of a file, if it has any
+fn open_exif(filename: &str, mmap: bool) -> Option<exif::Exif> {
+    if !has_exif_container(filename) {
+        return None;
+    }
+    let file = match File::open(filename) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!("Could not open file {}: {}", filename, e);
+            return None;
+        }
+    };
+    if mmap {
+        if let Some(exif) = exif_mmap::read_header(&file) {
+            return Some(exif);
+        }
+    }
+    let mut bufreader = std::io::BufReader::new(&file);
+    let exifreader = exif::Reader::new();
+    match exifreader.read_from_container(&mut bufreader) {
+        Ok(exif) => Some(exif),
+        Err(e) => {
+            debug!("No exif data in {}: {}", filename, e);
+            None
+        }
+    }
+}
+
+/// Folder below the target for files with unparsable metadata
+pub const CORRUPT_FOLDER: &str = "corrupt";
+
+// The error of a file whose exif data is broken, files without exif data are fine
+fn exif_parse_error(filename: &str) -> Option<exif::Error> {
+    if !has_exif_container(filename) {
+        return None;
+    }
+    let file = File::open(filename).ok()?;
+    let mut bufreader = std::io::BufReader::new(&file);
+    match exif::Reader::new().read_from_container(&mut bufreader) {
+        Ok(_) | Err(exif::Error::NotFound(_)) => None,
+        Err(e) => Some(e),
+    }
+}
+
+// Read a text tag without the quoting of display_value
+fn ascii_field(exif: &exif::Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        exif::Value::Ascii(values) => values
+            .first()
+            .map(|value| String::from_utf8_lossy(value).trim().to_string())
+            .filter(|value| !value.is_empty()),
+        _ => None,
+    }
+}
+
+// Fall back to the date the file was last modified
+fn read_modified_date(filename: &str) -> Option<String> {
+    let modified = fs::metadata(filename).and_then(|m| m.modified()).ok()?;
+    let datetime: DateTime<Local> = modified.into();
+    Some(dated_target(
+        &datetime.format("%Y/%m/%d").to_string(),
+        filename,
+    ))
+}
+
+fn file_name(filename: &str) -> &str {
+    Path::new(filename)
+        .file_name()
+        .expect("no filename")
+        .to_str()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::testutil::{ascii, jpeg_with_exif};
+
+    macro_rules! test_case {
+        ($fname:expr) => {
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/", $fname)
+        };
+    }
+
+    #[test]
+    fn strict_formats_fail_before_copying() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+        fs::File::create(source.join("IMG_0001.CR3")).unwrap();
+        fs::File::create(source.join("notes.txt")).unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.strict_formats = true;
+        let error = mediaconfig.copy_media_files().unwrap_err();
+        assert_eq!(
+            "found 1 media files of a format this build cannot sort",
+            error.to_string()
+        );
+        assert!(!target.exists());
+
+        fs::remove_file(source.join("IMG_0001.CR3")).unwrap();
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.strict_formats = true;
+        assert_eq!(1, mediaconfig.copy_media_files().unwrap().copied);
+    }
+
+    #[test]
+    fn weekday_folders() {
+        assert_eq!(
+            Some(String::from("Saturday")),
+            weekday_folder("2021/01/30/IMG_1.jpg", &[])
+        );
+        assert_eq!(
+            Some(String::from("Monday")),
+            weekday_folder("2024/02/26/IMG_1.jpg", &[])
+        );
+        let german: Vec<String> = "Montag,Dienstag,Mittwoch,Donnerstag,Freitag,Samstag,Sonntag"
+            .split(',')
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            Some(String::from("Samstag")),
+            weekday_folder("2021/01/30/IMG_1.jpg", &german)
+        );
+        assert_eq!(None, weekday_folder("2005/07/scan.jpg", &[]));
+
+        let options = Options {
+            with_weekday: true,
+            granularity: Granularity::Month,
+            ..Default::default()
+        };
+        let entry = MediaEntry::dated(String::from("2021/01/30/IMG_1.jpg"), DateSource::Exif);
+        assert_eq!(
+            "2021/01/Saturday/IMG_1.jpg",
+            place("IMG_1.jpg", entry, &options).target
+        );
+    }
+
+    #[test]
+    fn test_is_media_file() {
+        let list_of_media_files = vec![
+            "jpg", "jpeg", "mp4", "mov", "png", "webp", "avif", "heic", "heif", "dng", "nef",
+            "cr2", "JPG", "JPEG", "MP4", "MOV", "PNG", "WEBP", "AVIF", "HEIC", "HEIF", "DNG",
+            "NEF", "CR2", "mts", "M2TS",
+        ];
+        for media_file in list_of_media_files {
+            let filename = format!("test.{}", media_file);
+            assert!(
+                is_media_file(Path::new(&filename)),
+                "File should be a media file {}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_exif() {
+        let filename = test_case!("test_image.JPG");
+        assert_eq!(
+            Some(String::from("2022/12/17/test_image.JPG")),
+            read_exif(filename, false)
+        );
+        let options = Options {
+            mmap_exif: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(String::from("2022/12/17/test_image.JPG")),
+            Strategy::Exif.target_path(filename, &options)
+        );
+    }
+
+    #[test]
+    fn exif_dates_fall_back_in_order() {
+        let tmpdir = TempDir::new().unwrap();
+        let cases: [(&str, &[exif::Field], Option<&str>); 4] = [
+            (
+                "original.jpg",
+                &[
+                    ascii(Tag::DateTime, "2023:03:03 12:00:00"),
+                    ascii(Tag::DateTimeDigitized, "2022:02:02 12:00:00"),
+                    ascii(Tag::DateTimeOriginal, "2021:01:01 12:00:00"),
+                ],
+                Some("2021/01/01/original.jpg"),
+            ),
+            (
+                "digitized.jpg",
+                &[
+                    ascii(Tag::DateTime, "2023:03:03 12:00:00"),
+                    ascii(Tag::DateTimeDigitized, "2022:02:02 12:00:00"),
+                ],
+                Some("2022/02/02/digitized.jpg"),
+            ),
+            (
+                "modified.jpg",
+                &[ascii(Tag::DateTime, "2023:03:03 12:00:00")],
+                Some("2023/03/03/modified.jpg"),
+            ),
+            ("none.jpg", &[ascii(Tag::Model, "X100")], None),
+        ];
+        for (name, fields, expected) in cases {
+            let image = tmpdir.path().join(name);
+            fs::write(&image, jpeg_with_exif(fields)).unwrap();
+            assert_eq!(
+                expected.map(String::from),
+                read_exif(image.to_str().unwrap(), false),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn webp_without_exif_falls_back_to_mtime() {
+        let tmpdir = TempDir::new().unwrap();
+        let image = tmpdir.path().join("download.webp");
+        let file = fs::File::create(&image).unwrap();
+        // noon in the local timezone, as the mtime is dated in local time
+        let modified = NaiveDate::from_ymd_opt(2022, 6, 15)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .and_then(|time| time.and_local_timezone(Local).single())
+            .unwrap();
+        file.set_modified(modified.into()).unwrap();
+
+        let image = image.to_str().unwrap();
+        assert_eq!(None, read_exif(image, false));
+        assert_eq!(
+            Some(String::from("2022/06/15/download.webp")),
+            target_path(image, &Options::default()).map(|entry| entry.target)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn date_command_runs_with_or_without_mtime() {
+        let tmpdir = TempDir::new().unwrap();
+        let image = tmpdir.path().join("download.webp");
+        fs::File::create(&image).unwrap();
+        let image = image.to_str().unwrap();
+
+        for order in [
+            vec![Strategy::Exif, Strategy::Mtime],
+            vec![Strategy::Exif, Strategy::Directory],
+        ] {
+            let options = Options {
+                strategy_order: order,
+                date_command: Some(String::from("echo 2021-03-04")),
+                ..Default::default()
+            };
+            assert_eq!(
+                Some(MediaEntry::dated(
+                    String::from("2021/03/04/download.webp"),
+                    DateSource::DateCommand
+                )),
+                target_path(image, &options)
+            );
+        }
+    }
+
+    #[test]
+    fn by_serial_inserts_sanitized_serial() {
+        let tmpdir = TempDir::new().unwrap();
+        let with_serial = tmpdir.path().join("with_serial.jpg");
+        let without_serial = tmpdir.path().join("without_serial.jpg");
+        fs::write(
+            &with_serial,
+            jpeg_with_exif(&[
+                ascii(Tag::DateTimeOriginal, "2023:06:10 14:00:00"),
+                ascii(Tag::BodySerialNumber, " BH/4711 "),
+            ]),
+        )
+        .unwrap();
+        fs::write(
+            &without_serial,
+            jpeg_with_exif(&[ascii(Tag::DateTimeOriginal, "2023:06:10 15:00:00")]),
+        )
+        .unwrap();
+        let options = Options {
+            by_serial: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Some(String::from("2023/06/10/BH_4711/with_serial.jpg")),
+            target_path(with_serial.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+        assert_eq!(
+            Some(String::from("2023/06/10/unknown-serial/without_serial.jpg")),
+            target_path(without_serial.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+    }
+
+    #[test]
+    fn by_lens_inserts_sanitized_lens() {
+        let tmpdir = TempDir::new().unwrap();
+        let with_lens = tmpdir.path().join("with_lens.jpg");
+        let without_lens = tmpdir.path().join("without_lens.jpg");
+        fs::write(
+            &with_lens,
+            jpeg_with_exif(&[
+                ascii(Tag::DateTimeOriginal, "2023:06:10 14:00:00"),
+                ascii(Tag::LensModel, "RF24-105mm F4 L IS USM"),
+            ]),
+        )
+        .unwrap();
+        fs::write(
+            &without_lens,
+            jpeg_with_exif(&[ascii(Tag::DateTimeOriginal, "2023:06:10 15:00:00")]),
+        )
+        .unwrap();
+        let options = Options {
+            by_lens: true,
+            granularity: Granularity::Month,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Some(String::from("2023/06/RF24-105mm_F4_L_IS_USM/with_lens.jpg")),
+            target_path(with_lens.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+        assert_eq!(
+            Some(String::from("2023/06/unknown-lens/without_lens.jpg")),
+            target_path(without_lens.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+    }
+
+    #[test]
+    fn by_aspect_classifies_orientation() {
+        let tmpdir = TempDir::new().unwrap();
+        let options = Options {
+            by_aspect: true,
+            ..Default::default()
+        };
+        let target = |name: &str| {
+            target_path(tmpdir.path().join(name).to_str().unwrap(), &options)
+                .map(|entry| entry.target)
+        };
+        for (name, width, height) in [
+            ("IMG_20210130_000001.jpg", 48, 64),
+            ("IMG_20210130_000002.jpg", 64, 48),
+            ("IMG_20210130_000003.jpg", 64, 64),
+        ] {
+            image::RgbImage::new(width, height)
+                .save(tmpdir.path().join(name))
+                .unwrap();
+        }
+        fs::File::create(tmpdir.path().join("VID_20210130_000004.mp4")).unwrap();
+
+        assert_eq!(
+            Some(String::from("2021/01/30/portrait/IMG_20210130_000001.jpg")),
+            target("IMG_20210130_000001.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2021/01/30/landscape/IMG_20210130_000002.jpg")),
+            target("IMG_20210130_000002.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2021/01/30/square/IMG_20210130_000003.jpg")),
+            target("IMG_20210130_000003.jpg")
+        );
+        // videos are exempt by default, and this one has no readable size anyway
+        assert_eq!(
+            Some(String::from("2021/01/30/VID_20210130_000004.mp4")),
+            target("VID_20210130_000004.mp4")
+        );
+    }
+
+    #[test]
+    fn split_by_ext_adds_extension_folder() {
+        let tmpdir = TempDir::new().unwrap();
+        let photo = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let video = tmpdir.path().join("VID_20210130_000002.mp4");
+        fs::File::create(&photo).unwrap();
+        fs::File::create(&video).unwrap();
+        let options = Options {
+            split_by_ext: true,
+            granularity: Granularity::Month,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Some(String::from("2021/01/jpg/IMG_20210130_000001.jpg")),
+            target_path(photo.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+        assert_eq!(
+            Some(String::from("2021/01/mp4/VID_20210130_000002.mp4")),
+            target_path(video.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+    }
+
+    #[test]
+    fn normalize_unicode_merges_nfc_and_nfd() {
+        let tmpdir = TempDir::new().unwrap();
+        let composed = tmpdir.path().join("2021-01-30/caf\u{e9}.jpg");
+        let decomposed = tmpdir.path().join("2021-01-30/cafe\u{301}.jpg");
+        let options = Options {
+            normalize_unicode: true,
+            ..Default::default()
+        };
+        let expected = Some(String::from("2021/01/30/caf\u{e9}.jpg"));
+        for path in [composed, decomposed] {
+            assert_eq!(
+                expected,
+                target_path(path.to_str().unwrap(), &options).map(|entry| entry.target)
+            );
+        }
+    }
+
+    #[test]
+    fn by_person_prefixes_people_folder() {
+        let tmpdir = TempDir::new().unwrap();
+        let family = tmpdir.path().join("IMG_20210130_000001.jpg");
+        fs::write(
+            &family,
+            br#"<rdf:Description mwg-rs:Name="Zoe"/><rdf:Description mwg-rs:Name="Anna Lee"/>"#,
+        )
+        .unwrap();
+        let nobody = tmpdir.path().join("IMG_20210130_000002.jpg");
+        fs::File::create(&nobody).unwrap();
+        let target = |path: &Path, person_rule| {
+            let options = Options {
+                by_person: true,
+                person_rule,
+                ..Default::default()
+            };
+            target_path(path.to_str().unwrap(), &options).map(|entry| entry.target)
+        };
+
+        assert_eq!(
+            Some(String::from(
+                "people/Zoe/2021/01/30/IMG_20210130_000001.jpg"
+            )),
+            target(&family, PersonRule::First)
+        );
+        assert_eq!(
+            Some(String::from(
+                "people/Anna_Lee/2021/01/30/IMG_20210130_000001.jpg"
+            )),
+            target(&family, PersonRule::Alphabetical)
+        );
+        assert_eq!(
+            Some(String::from(
+                "people/Anna_Lee+Zoe/2021/01/30/IMG_20210130_000001.jpg"
+            )),
+            target(&family, PersonRule::All)
+        );
+        assert_eq!(
+            Some(String::from("2021/01/30/IMG_20210130_000002.jpg")),
+            target(&nobody, PersonRule::First)
+        );
+    }
+
+    #[test]
+    fn test_sanitize_component() {
+        assert_eq!(Some(String::from("a_b_c")), sanitize_component(" a/b:c "));
+        assert_eq!(None, sanitize_component("../"));
+        assert_eq!(Some(String::from("123")), sanitize_component("123."));
+    }
+
+    #[test]
+    fn read_directory_dates() {
+        assert_eq!(
+            Some(String::from("2005/scan.jpg")),
+            read_directory_date("/scans/2005/scan.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2005/07/scan.jpg")),
+            read_directory_date("/scans/2005-07 Summer Trip/scan.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2005/07/scan.jpg")),
+            read_directory_date("/scans/Holiday 2005_07/scan.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2005/07/14/scan.jpg")),
+            read_directory_date("/scans/2005-07-14/scan.jpg")
+        );
+        assert_eq!(None, read_directory_date("/scans/Summer Trip/scan.jpg"));
+        assert_eq!(None, read_directory_date("/scans/123456/scan.jpg"));
+    }
+
+    #[test]
+    fn burst_folders_date_sequence_numbers() {
+        let tmpdir = TempDir::new().unwrap();
+        let burst = tmpdir.path().join("DCIM/Camera/2023-01-15 10.29.11 Burst");
+        fs::create_dir_all(&burst).unwrap();
+        let shot = burst.join("002.jpg");
+        fs::File::create(&shot).unwrap();
+
+        assert_eq!(
+            Some(MediaEntry::dated(
+                String::from("2023/01/15/2023-01-15_10.29.11_Burst_002.jpg"),
+                DateSource::BurstFolder
+            )),
+            target_path(shot.to_str().unwrap(), &Options::default())
+        );
+        assert_eq!(None, read_burst_date("/scans/2005-07 Summer Trip/001.jpg"));
+        assert_eq!(None, read_burst_date("/scans/2005-07-14/scan.jpg"));
+    }
+
+    #[test]
+    fn day_cutoff_dates_early_captures_a_day_earlier() {
+        let options = Options {
+            day_cutoff: parse_day_cutoff("04:00").ok(),
+            ..Default::default()
+        };
+        let target = |filename: &str| target_path(filename, &options).map(|entry| entry.target);
+        assert_eq!(
+            Some(String::from("2021/01/30/IMG_20210131_020000.jpg")),
+            target("IMG_20210131_020000.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2021/01/31/IMG_20210131_050000.jpg")),
+            target("IMG_20210131_050000.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2020/12/31/IMG_20210101_000100.jpg")),
+            target("IMG_20210101_000100.jpg")
+        );
+        assert!(parse_day_cutoff("4 am").is_err());
+    }
+
+    #[test]
+    fn screenshots_go_to_their_own_target() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        let screenshots = tmpdir.path().join("screenshots");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::File::create(source.join("Screenshot 2023-01-15 at 10.29.11.png")).unwrap();
+        fs::File::create(source.join("Screenshot_20230115-102911.jpg")).unwrap();
+        fs::File::create(source.join("export.png")).unwrap();
+        fs::File::create(source.join("IMG_20230115_102911.jpg")).unwrap();
+        fs::write(
+            source.join("camera.png"),
+            jpeg_with_exif(&[ascii(Tag::Model, "X100")]),
+        )
+        .unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.screenshots_target = Some(screenshots.clone());
+        mediaconfig.copy_media_files().unwrap();
+
+        assert!(screenshots
+            .join("2023/01/15/Screenshot 2023-01-15 at 10.29.11.png")
+            .exists());
+        assert!(target.join("2023/01/15/IMG_20230115_102911.jpg").exists());
+        assert!(!screenshots
+            .join("2023/01/15/IMG_20230115_102911.jpg")
+            .exists());
+        assert!(!is_screenshot(
+            source.join("camera.png").to_str().unwrap(),
+            false
+        ));
+        assert!(is_screenshot(
+            source
+                .join("Screenshot_20230115-102911.jpg")
+                .to_str()
+                .unwrap(),
+            false
+        ));
+        assert!(is_screenshot(
+            source.join("export.png").to_str().unwrap(),
+            false
+        ));
+    }
+
+    #[test]
+    fn strategies_name_targets_alike() {
+        let tmpdir = TempDir::new().unwrap();
+        let name = |target: Option<String>| target.map(|target| file_name(&target).to_string());
+
+        let copy = tmpdir.path().join("Copy of IMG_20230115_102911.jpg");
+        fs::File::create(&copy).unwrap();
+        let copy = copy.to_str().unwrap();
+        assert_eq!(
+            Some(String::from("Copy of IMG_20230115_102911.jpg")),
+            name(Strategy::Filename.target_path(copy, &Options::default()))
+        );
+        assert_eq!(
+            name(Strategy::Filename.target_path(copy, &Options::default())),
+            name(Strategy::Mtime.target_path(copy, &Options::default()))
+        );
+
+        let burst = tmpdir.path().join("2023-01-15 Burst");
+        fs::create_dir_all(&burst).unwrap();
+        let shot = burst.join("001.jpg");
+        fs::write(
+            &shot,
+            jpeg_with_exif(&[ascii(Tag::DateTimeOriginal, "2023:01:15 10:29:11")]),
+        )
+        .unwrap();
+        let shot = shot.to_str().unwrap();
+        assert_eq!(
+            Some(String::from("2023-01-15_Burst_001.jpg")),
+            name(Strategy::Burst.target_path(shot, &Options::default()))
+        );
+        assert_eq!(
+            name(Strategy::Burst.target_path(shot, &Options::default())),
+            name(Strategy::Exif.target_path(shot, &Options::default()))
+        );
+    }
+
+    #[test]
+    fn camera_offset_shifts_exif_date() {
+        let tmpdir = TempDir::new().unwrap();
+        let late = tmpdir.path().join("late.jpg");
+        let other = tmpdir.path().join("other.jpg");
+        fs::write(
+            &late,
+            jpeg_with_exif(&[
+                ascii(Tag::DateTimeOriginal, "2023:06:10 23:30:00"),
+                ascii(Tag::Model, "E-M10MarkII"),
+            ]),
+        )
+        .unwrap();
+        fs::write(
+            &other,
+            jpeg_with_exif(&[
+                ascii(Tag::DateTimeOriginal, "2023:06:10 23:30:00"),
+                ascii(Tag::Model, "EOS R6"),
+            ]),
+        )
+        .unwrap();
+        let options = Options {
+            camera_offsets: vec![parse_camera_offset("E-M10MarkII=+01:15").unwrap()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Some(MediaEntry::dated(
+                String::from("2023/06/11/late.jpg"),
+                DateSource::Exif
+            )),
+            target_path(late.to_str().unwrap(), &options)
+        );
+        assert_eq!(
+            Some(String::from("2023/06/10/other.jpg")),
+            target_path(other.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+        // edited copies may only carry the later dates
+        fs::write(
+            &late,
+            jpeg_with_exif(&[
+                ascii(Tag::DateTimeDigitized, "2023:06:10 23:30:00"),
+                ascii(Tag::Model, "E-M10MarkII"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            Some(String::from("2023/06/11/late.jpg")),
+            target_path(late.to_str().unwrap(), &options).map(|entry| entry.target)
+        );
+        assert_eq!(
+            Ok((String::from("1234"), TimeDelta::seconds(-5430))),
+            parse_camera_offset("1234=-1:30:30")
+        );
+        assert!(parse_camera_offset("E-M10=+1h").is_err());
+        assert!(parse_camera_offset("=+01:00").is_err());
+    }
+
+    #[test]
+    fn thumbnail_dates_are_ignored() {
+        let tmpdir = TempDir::new().unwrap();
+        let thumbnail = |value: &str| exif::Field {
+            ifd_num: In::THUMBNAIL,
+            ..ascii(Tag::DateTimeOriginal, value)
+        };
+        let both = tmpdir.path().join("both.jpg");
+        let only_thumbnail = tmpdir.path().join("only_thumbnail.jpg");
+        fs::write(
+            &both,
+            jpeg_with_exif(&[
+                thumbnail("2015:03:01 09:00:00"),
+                ascii(Tag::DateTimeOriginal, "2022:08:21 18:00:00"),
+            ]),
+        )
+        .unwrap();
+        fs::write(
+            &only_thumbnail,
+            jpeg_with_exif(&[
+                ascii(Tag::Model, "E-M10MarkII"),
+                thumbnail("2015:03:01 09:00:00"),
+            ]),
+        )
+        .unwrap();
+
+        let exif = open_exif(both.to_str().unwrap(), false).unwrap();
+        assert!(exif
+            .get_field(Tag::DateTimeOriginal, In::THUMBNAIL)
+            .is_some());
+        assert_eq!(
+            Some(String::from("2022/08/21/both.jpg")),
+            read_exif(both.to_str().unwrap(), false)
+        );
+        assert_eq!(None, read_exif(only_thumbnail.to_str().unwrap(), false));
+    }
+
+    #[test]
+    fn strategy_order_is_respected() {
+        let tmpdir = TempDir::new().unwrap();
+        let folder = tmpdir.path().join("2005-07 Summer Trip");
+        create_dir(folder.to_str().unwrap()).unwrap();
+        let image = folder.join("IMG_20210130_000001.jpg");
+        fs::File::create(&image).unwrap();
+        let image = image.to_str().unwrap();
+
+        assert_eq!(
+            Some(String::from("2021/01/30/IMG_20210130_000001.jpg")),
+            target_path(image, &Options::default()).map(|entry| entry.target)
+        );
+        let options = Options {
+            strategy_order: vec![Strategy::Directory, Strategy::Filename],
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(MediaEntry::dated(
+                String::from("2005/07/IMG_20210130_000001.jpg"),
+                DateSource::DirectoryName
+            )),
+            target_path(image, &options)
+        );
+    }
+
+    #[test]
+    fn images_and_videos_have_own_strategy_orders() {
+        let tmpdir = TempDir::new().unwrap();
+        let photo = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let video = tmpdir.path().join("VID_20210130_000002.mp4");
+        fs::write(
+            &photo,
+            jpeg_with_exif(&[ascii(Tag::DateTimeOriginal, "2019:07:04 12:00:00")]),
+        )
+        .unwrap();
+        fs::File::create(&video).unwrap();
+        let options = Options {
+            image_date: vec![Strategy::Exif, Strategy::Mtime],
+            video_date: vec![Strategy::Mtime],
+            ..Default::default()
+        };
+        let today = Local::now().format("%Y/%m/%d").to_string();
+        let source = |path: &PathBuf| {
+            target_path(path.to_str().unwrap(), &options).map(|entry| entry.date_source)
+        };
+
+        assert_eq!(Some(Some(DateSource::Exif)), source(&photo));
+        assert_eq!(Some(Some(DateSource::Mtime)), source(&video));
+        assert!(target_path(video.to_str().unwrap(), &options)
+            .unwrap()
+            .target
+            .starts_with(&today));
+        // without their own order both use the filename first
+        let options = Options::default();
+        for path in [&photo, &video] {
+            assert_eq!(
+                Some(DateSource::SmartphoneName),
+                target_path(path.to_str().unwrap(), &options)
+                    .unwrap()
+                    .date_source
+            );
+        }
+    }
+
+    #[test]
+    fn filename_dates_only_for_trusted_extensions() {
+        let tmpdir = TempDir::new().unwrap();
+        let image = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let video = tmpdir.path().join("VID_20210130_000001.mp4");
+        fs::File::create(&image).unwrap();
+        fs::File::create(&video).unwrap();
+
+        let options = Options {
+            filename_date_ext: vec![String::from("mp4")],
+            ..Default::default()
+        };
+        let source = |path: &Path| target_path(path.to_str().unwrap(), &options)?.date_source;
+        assert_eq!(Some(DateSource::SmartphoneName), source(&video));
+        assert_eq!(Some(DateSource::Mtime), source(&image));
+    }
+
+    #[test]
+    fn epoch_millis_names() {
+        let date = DateTime::from_timestamp_millis(1673778551000)
+            .unwrap()
+            .with_timezone(&Local)
+            .format("%Y/%m/%d");
+        assert_eq!(
+            Some(format!("{}/content_1673778551000.jpg", date)),
+            smartphone_file("/sdcard/content_1673778551000.jpg")
+        );
+        assert_eq!(
+            Some(format!("{}/1673778551000.mp4", date)),
+            smartphone_file("1673778551000.mp4")
+        );
+        // MediaStore IDs and epochs outside of 2000 until now are no dates
+        assert_eq!(None, smartphone_file("1000012345.jpg"));
+        assert_eq!(None, smartphone_file("0915148800000.jpg"));
+        assert_eq!(None, smartphone_file("9999999999999.jpg"));
+        assert_eq!(None, smartphone_file("photo_1673778551000_edit.jpg"));
+    }
+
+    #[test]
+    fn oldest_date_of_all_strategies() {
+        let tmpdir = TempDir::new().unwrap();
+        let folder = tmpdir.path().join("2021-01 Edits");
+        create_dir(folder.to_str().unwrap()).unwrap();
+        let image = folder.join("IMG_20210130_000001.jpg");
+        fs::write(
+            &image,
+            jpeg_with_exif(&[ascii(Tag::DateTimeOriginal, "2021:01:29 23:00:00")]),
+        )
+        .unwrap();
+        let options = Options {
+            oldest_date: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(MediaEntry::dated(
+                String::from("2021/01/29/IMG_20210130_000001.jpg"),
+                DateSource::Exif
+            )),
+            target_path(image.to_str().unwrap(), &options)
+        );
+        assert!(date_key("2005/07/scan.jpg") > date_key("2005/07/31/scan.jpg"));
+        assert!(date_key("2005/scan.jpg") < date_key("2006/01/01/scan.jpg"));
+    }
+
+    #[test]
+    fn screenshot_names() {
+        assert_eq!(
+            Some(String::from(
+                "2023/01/15/Screenshot 2023-01-15 at 10.29.11.png"
+            )),
+            smartphone_file("/Desktop/Screenshot 2023-01-15 at 10.29.11.png")
+        );
+        assert_eq!(
+            Some(String::from(
+                "2019/05/01/Screen Shot 2019-05-01 at 9.02.13 PM.png"
+            )),
+            smartphone_file("Screen Shot 2019-05-01 at 9.02.13 PM.png")
+        );
+        assert_eq!(
+            Some(String::from("2023/01/15/Screenshot 2023-01-15 102911.png")),
+            smartphone_file("Screenshot 2023-01-15 102911.png")
+        );
+        assert_eq!(None, smartphone_file("Screenshot (12).png"));
+        assert_eq!(
+            None,
+            smartphone_file("Screenshot 2023-13-45 at 10.29.11.png")
+        );
+    }
+
+    #[test]
+    fn signal_names() {
+        assert_eq!(
+            Some(String::from("2023/01/15/signal-2023-01-15-102911.jpg")),
+            smartphone_file("/Signal/signal-2023-01-15-102911.jpg")
+        );
+        assert_eq!(
+            Some(String::from(
+                "2023/01/15/signal-2023-01-15-10-29-11-123.jpg"
+            )),
+            smartphone_file("signal-2023-01-15-10-29-11-123.jpg")
+        );
+        assert_eq!(None, smartphone_file("signal-2023-02-30-102911.jpg"));
+    }
+
+    #[test]
+    fn telegram_names() {
+        assert_eq!(
+            Some(String::from("2023/01/15/photo_2023-01-15_10-29-11.jpg")),
+            smartphone_file("/Telegram Desktop/photo_2023-01-15_10-29-11.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2023/01/15/video_2023-01-15_10-29-11.mp4")),
+            smartphone_file("video_2023-01-15_10-29-11.mp4")
+        );
+        assert_eq!(None, smartphone_file("photo_2023-01-15.jpg"));
+    }
+
+    #[test]
+    fn whatsapp_names() {
+        assert_eq!(
+            Some(String::from("2023/01/15/IMG-20230115-WA0001.jpg")),
+            smartphone_file("/WhatsApp Images/IMG-20230115-WA0001.jpg")
+        );
+        assert_eq!(
+            Some(String::from("2023/01/15/VID-20230115-WA0012.mp4")),
+            smartphone_file("VID-20230115-WA0012.mp4")
+        );
+        assert_eq!(None, smartphone_file("VID-20231315-WA0012.mp4"));
+        assert_eq!(None, smartphone_file("VID-20230115-0012.mp4"));
+    }
+
+    #[test]
+    fn read_no_smartphone_image() {
+        let filename = "no_match.jpg";
+        assert_eq!(None, smartphone_file(filename));
+    }
+    #[test]
+    fn read_smartphone_video() {
+        let filename = "VID_20221220_170102.jpg";
+        assert_eq!(
+            Some(format!("2022/12/20/{filename}")),
+            smartphone_file(filename)
+        );
+    }
+    #[test]
+    fn read_smartphone_image() {
+        let filename = "IMG_20230115_102911.jpg";
+        assert_eq!(
+            Some(format!("2023/01/15/{filename}")),
+            smartphone_file(filename)
+        );
+    }
+
+    #[test]
+    fn find_all_media_files_recursive() {
+        let tmpdir = TempDir::new().unwrap();
+        let test_images = tmpdir.path().join("test_images");
+        //let target_images = tmpdir.path().join("target_images");
+        create_dir(test_images.to_str().unwrap()).unwrap();
+        let test_media_files = [
+            "IMG_20210130_000001.jpg",
+            "IMG_20210130_000002.jpg",
+            "VID_20210130_000003.mp4",
+        ];
+        for file in test_media_files.iter() {
+            fs::File::create(test_images.join(file)).expect("Just create the test files");
+        }
+
+        let mut mediaconfig = MediaConfig::new(
+            test_images.to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig
+            .find_all_media_files(Some(tmpdir.path().to_str().unwrap()), true)
+            .expect("Everything works as intended");
+        assert_eq!(test_media_files.len(), mediaconfig.files.len());
+
+        let targets: Vec<String> = mediaconfig
+            .files
+            .into_values()
+            .map(|entry| entry.target)
+            .collect();
+        for file in test_media_files.iter() {
+            assert!(targets.contains(&format!("2021/01/30/{file}")));
+        }
+
+        tmpdir.close().expect("Remove test directory");
+    }
+
+    #[test]
+    fn parallel_scan_matches_single_threaded() {
+        let tmpdir = TempDir::new().unwrap();
+        for day in 1..=20 {
+            let folder = tmpdir.path().join(format!("{:02}", day % 3));
+            fs::create_dir_all(&folder).unwrap();
+            fs::File::create(folder.join(format!("IMG_202101{:02}_000001.jpg", day))).unwrap();
+        }
+        let scan = |threads| {
+            let mut mediaconfig = MediaConfig::new(
+                tmpdir.path().to_str().unwrap().to_string(),
+                tmpdir.path().join("target_images"),
+            );
+            mediaconfig.options.scan_threads = Some(threads);
+            mediaconfig.find_all_media_files(None, true).unwrap();
+            mediaconfig.files
+        };
+        let single = scan(1);
+        assert_eq!(20, single.len());
+        assert_eq!(single, scan(4));
+    }
+
+    #[test]
+    fn scan_cache_reuses_dates_of_unchanged_files() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+        let cache = tmpdir.path().join("cache.json");
+        let scan = || {
+            let mut mediaconfig = MediaConfig::new(
+                source.to_str().unwrap().to_string(),
+                tmpdir.path().join("target"),
+            );
+            mediaconfig.options.scan_cache = Some(cache.clone());
+            mediaconfig.options.granularity = Granularity::Month;
+            mediaconfig.find_all_media_files(None, true).unwrap();
+            mediaconfig.files.into_values().next().unwrap().target
+        };
+        assert_eq!("2021/01/IMG_20210130_000001.jpg", scan());
+
+        // a date only the cache knows proves it was not read again
+        let cached = fs::read_to_string(&cache).unwrap();
+        fs::write(&cache, cached.replace("2021/01/30/", "1999/12/31/")).unwrap();
+        assert_eq!("1999/12/IMG_20210130_000001.jpg", scan());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names_are_skipped() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let tmpdir = TempDir::new().unwrap();
+        fs::File::create(tmpdir.path().join(OsStr::from_bytes(b"IMG_\xff.jpg"))).unwrap();
+        fs::create_dir(tmpdir.path().join(OsStr::from_bytes(b"caf\xe9"))).unwrap();
+        fs::File::create(tmpdir.path().join("IMG_20210130_000001.jpg")).unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.find_all_media_files(None, true).unwrap();
+        assert_eq!(1, mediaconfig.files.len());
+    }
+
+    #[test]
+    fn panicking_scan_is_skipped() {
+        assert_eq!(Some(1), catch_panic("ok.jpg", || Some(1)));
+        assert_eq!(
+            None::<u8>,
+            catch_panic("bad.jpg", || panic!("malformed IFD"))
+        );
+    }
+
+    #[test]
+    fn min_resolution_skips_thumbnails() {
+        let tmpdir = TempDir::new().unwrap();
+        let thumbnail = tmpdir.path().join("IMG_20210130_000001.png");
+        let photo = tmpdir.path().join("IMG_20210130_000002.png");
+        image::RgbImage::new(16, 16).save(&thumbnail).unwrap();
+        image::RgbImage::new(64, 48).save(&photo).unwrap();
+        fs::File::create(tmpdir.path().join("VID_20210130_000003.mp4")).unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.options.min_resolution = Some((64, 48));
+        mediaconfig.find_all_media_files(None, true).unwrap();
+        assert_eq!(2, mediaconfig.files.len());
+        assert!(!mediaconfig.files.contains_key(thumbnail.to_str().unwrap()));
+    }
+
+    #[test]
+    fn exposure_filters_read_exif() {
+        let tmpdir = TempDir::new().unwrap();
+        let field = |tag, value| exif::Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        };
+        let rational = |num, denom| exif::Value::Rational(vec![exif::Rational { num, denom }]);
+        let night = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let day = tmpdir.path().join("IMG_20210130_000002.jpg");
+        fs::write(
+            &night,
+            jpeg_with_exif(&[
+                field(Tag::PhotographicSensitivity, exif::Value::Short(vec![3200])),
+                field(Tag::FNumber, rational(18, 10)),
+                field(Tag::FocalLength, rational(50, 1)),
+            ]),
+        )
+        .unwrap();
+        fs::write(
+            &day,
+            jpeg_with_exif(&[field(
+                Tag::PhotographicSensitivity,
+                exif::Value::Short(vec![100]),
+            )]),
+        )
+        .unwrap();
+        fs::File::create(tmpdir.path().join("IMG_20210130_000003.jpg")).unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.options.exposure_filter = ExposureFilter {
+            min_iso: Some(1600),
+            max_aperture: Some(2.0),
+            min_focal_length: Some(35.0),
+        };
+        mediaconfig.find_all_media_files(None, true).unwrap();
+        assert_eq!(
+            vec![night.to_str().unwrap()],
+            mediaconfig.files.keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn listed_hashes_are_skipped() {
+        let tmpdir = TempDir::new().unwrap();
+        fs::write(tmpdir.path().join("IMG_20210130_000001.jpg"), b"image").unwrap();
+        fs::write(tmpdir.path().join("IMG_20210130_000002.jpg"), b"other").unwrap();
+        let list = tmpdir.path().join("archived.sha256");
+        fs::write(
+            &list,
+            "6105D6CC76AF400325E94D588CE511BE5BFDBB73B437DC51ECA43917D7A43E3D  IMG_1.jpg\n\n",
+        )
+        .unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.options.skip_hashes = checksum::read_hash_list(&list).unwrap();
+        mediaconfig.find_all_media_files(None, true).unwrap();
+        assert_eq!(1, mediaconfig.files.len());
+        assert!(mediaconfig
+            .files
+            .keys()
+            .all(|source| source.ends_with("IMG_20210130_000002.jpg")));
+    }
+
+    #[test]
+    fn identical_skips_are_recorded_as_aliases() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let folder = tmpdir.path().join("target/2021/01/30");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&folder).unwrap();
+        for (name, content, existing) in [
+            ("IMG_20210130_000001.jpg", "image", "image"),
+            ("IMG_20210130_000002.jpg", "edited", "pixels"),
+        ] {
+            fs::write(source.join(name), content).unwrap();
+            fs::write(folder.join(name), existing).unwrap();
+        }
+
+        let mut mediaconfig = MediaConfig::new(
+            source.to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        mediaconfig.options.write_index = true;
+        mediaconfig.options.dedupe_keep_original_location = true;
+        let summary = mediaconfig.copy_media_files().unwrap();
+        assert_eq!((1, 1), (summary.copied, summary.skipped));
+
+        let index = index::FolderIndex::load(&folder);
+        assert_eq!(
+            vec!["IMG_20210130_000001.jpg"],
+            index.aliases.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![source.join("IMG_20210130_000001.jpg").to_str().unwrap()],
+            index.aliases["IMG_20210130_000001.jpg"]
+        );
+    }
+
+    #[test]
+    fn min_resolution_respects_orientation() {
+        let tmpdir = TempDir::new().unwrap();
+        let portrait = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let landscape = tmpdir.path().join("IMG_20210130_000002.jpg");
+        let orientation = |value| exif::Field {
+            tag: Tag::Orientation,
+            ifd_num: In::PRIMARY,
+            value: exif::Value::Short(vec![value]),
+        };
+        // both are stored as 64x48, the portrait one is displayed as 48x64
+        for (path, value) in [(&portrait, 6), (&landscape, 1)] {
+            let mut pixels = io::Cursor::new(Vec::new());
+            image::RgbImage::new(64, 48)
+                .write_to(&mut pixels, image::ImageFormat::Jpeg)
+                .unwrap();
+            let app1 = jpeg_with_exif(&[orientation(value)]);
+            let mut jpeg = pixels.into_inner();
+            jpeg.splice(2..2, app1[2..app1.len() - 2].iter().copied());
+            fs::write(path, jpeg).unwrap();
+        }
+        assert!(is_below_resolution(&portrait, (64, 48), false));
+        assert!(!is_below_resolution(&portrait, (48, 64), false));
+        assert!(!is_below_resolution(&landscape, (64, 48), false));
+    }
+
+    #[test]
+    fn atomic_copies_leave_no_temp_file() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let target = tmpdir.path().join("copy.jpg");
+        fs::write(&source, b"pixels").unwrap();
+
+        let options = Options {
+            atomic: true,
+            ..Default::default()
+        };
+        assert_eq!(6, copy_contents(&source, &target, &options).unwrap());
+        assert_eq!(b"pixels".to_vec(), fs::read(&target).unwrap());
+        assert!(copy_contents(&tmpdir.path().join("missing.jpg"), &target, &options).is_err());
+        let mut names: Vec<_> = fs::read_dir(tmpdir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(vec!["IMG_20210130_000001.jpg", "copy.jpg"], names);
+    }
+
+    #[test]
+    fn atomic_copies_use_temp_dir() {
+        let tmpdir = TempDir::new().unwrap();
+        let temp_dir = tmpdir.path().join("temp");
+        assert_eq!(
+            temp_dir.join("IMG_1.jpg.tmp"),
+            temp_path(Path::new("/photos/2021/IMG_1.jpg"), Some(&temp_dir))
+        );
+        assert_eq!(
+            PathBuf::from("/photos/2021/IMG_1.jpg.tmp"),
+            temp_path(Path::new("/photos/2021/IMG_1.jpg"), None)
+        );
+        if cfg!(unix) {
+            assert_eq!(Some(true), same_filesystem(&temp_dir, tmpdir.path()));
+        }
+
+        fs::File::create(tmpdir.path().join("IMG_20210130_000001.jpg")).unwrap();
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.options.atomic = true;
+        mediaconfig.options.temp_dir = Some(temp_dir.clone());
+        assert_eq!(1, mediaconfig.copy_media_files().unwrap().copied);
+        assert_eq!(0, fs::read_dir(&temp_dir).unwrap().count());
+    }
+
+    #[test]
+    fn detects_disk_full() {
+        let full: Box<dyn Error> = Box::new(io::Error::from(io::ErrorKind::StorageFull));
+        assert!(is_disk_full(full.as_ref()));
+        let denied: Box<dyn Error> = Box::new(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(!is_disk_full(denied.as_ref()));
+        if cfg!(target_os = "linux") {
+            let tmpdir = TempDir::new().unwrap();
+            let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+            fs::write(&source, [0; 4096]).unwrap();
+            let error: Box<dyn Error> = Box::new(fs::copy(&source, "/dev/full").unwrap_err());
+            assert!(is_disk_full(error.as_ref()));
+        }
+    }
+
+    #[test]
+    fn free_space_check() {
+        assert!(enough_space(Path::new("/mnt"), 10, 10).is_ok());
+        let error = enough_space(Path::new("/mnt"), 3 * 1024 * 1024 * 1024, 512 * 1024)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            "not enough free space in /mnt: 3.0 GB needed, 512.0 KB available",
+            error
+        );
+        assert_eq!("17 B", human_bytes(17));
+
+        let tmpdir = TempDir::new().unwrap();
+        fs::File::create(tmpdir.path().join("IMG_20210130_000001.jpg")).unwrap();
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("not/yet/there"),
+        );
+        mediaconfig.options.check_free_space = true;
+        assert_eq!(1, mediaconfig.copy_media_files().unwrap().copied);
+    }
+
+    #[test]
+    fn test_parse_resolution() {
+        assert_eq!(Ok((640, 480)), parse_resolution("640x480"));
+        assert_eq!(Ok((1920, 1080)), parse_resolution("1920X1080"));
+        assert!(parse_resolution("640").is_err());
+        assert!(parse_resolution("axb").is_err());
+    }
+
+    #[test]
+    fn non_recursive_scans_top_level_only() {
+        let tmpdir = TempDir::new().unwrap();
+        let nested = tmpdir.path().join("nested");
+        create_dir(nested.to_str().unwrap()).unwrap();
+        fs::File::create(tmpdir.path().join("IMG_20210130_000001.jpg")).unwrap();
+        fs::File::create(nested.join("IMG_20210130_000002.jpg")).unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.find_all_media_files(None, false).unwrap();
+        assert_eq!(1, mediaconfig.files.len());
+    }
+
+    #[test]
+    fn min_age_skips_recent_files() {
+        let tmpdir = TempDir::new().unwrap();
+        let settled = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let file = fs::File::create(&settled).unwrap();
+        file.set_modified(std::time::SystemTime::now() - Duration::from_secs(600))
+            .unwrap();
+        fs::File::create(tmpdir.path().join("IMG_20210130_000002.jpg")).unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.options.min_age = Some(Duration::from_secs(60));
+        mediaconfig.find_all_media_files(None, true).unwrap();
+        assert_eq!(
+            vec![settled.to_str().unwrap()],
+            mediaconfig.files.keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dedup_inodes_skips_hardlinks() {
+        let tmpdir = TempDir::new().unwrap();
+        let first = tmpdir.path().join("first");
+        let second = tmpdir.path().join("second");
+        create_dir(first.to_str().unwrap()).unwrap();
+        create_dir(second.to_str().unwrap()).unwrap();
+        fs::File::create(first.join("IMG_20210130_000001.jpg")).unwrap();
+        fs::hard_link(
+            first.join("IMG_20210130_000001.jpg"),
+            second.join("IMG_20210130_000001.jpg"),
+        )
+        .unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.find_all_media_files(None, true).unwrap();
+        assert_eq!(2, mediaconfig.files.len());
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target_images"),
+        );
+        mediaconfig.options.dedup_inodes = true;
+        mediaconfig.find_all_media_files(None, true).unwrap();
+        assert_eq!(1, mediaconfig.files.len());
+    }
+
+    #[test]
+    fn test_contains_media() {
+        let tmpdir = TempDir::new().unwrap();
+        let empty = tmpdir.path().join("empty/deeper");
+        let media = tmpdir.path().join("media/deeper");
+        create_dir(empty.to_str().unwrap()).unwrap();
+        create_dir(media.to_str().unwrap()).unwrap();
+        fs::File::create(empty.join("notes.txt")).unwrap();
+        fs::File::create(media.join("IMG_20210130_000001.jpg")).unwrap();
+
+        assert!(!contains_media(&tmpdir.path().join("empty")));
+        assert!(contains_media(&tmpdir.path().join("media")));
+    }
+
+    #[test]
+    fn copy_file_to_very_long_path() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+        fs::write(&source, b"image").unwrap();
+        let mut target = tmpdir.path().to_path_buf();
+        for _ in 0..6 {
+            target.push("a_rather_long_folder_name_from_a_camera_export");
+        }
+        target.push(format!("{}.jpg", "long_original_filename".repeat(4)));
+        assert!(target.to_str().unwrap().len() > 300);
+
+        let options = Options {
+            verify_size: true,
+            ..Default::default()
+        };
+        assert!(copy_file(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap());
+        assert_eq!(b"image".to_vec(), fs::read(&target).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_preserves_xattrs() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let target = tmpdir.path().join("copy/IMG_20210130_000001.jpg");
+        fs::write(&source, b"image").unwrap();
+        if xattr::set(&source, "user.mediasort.tag", b"red").is_err() {
+            // The file system of the temp dir has no user attributes
+            return;
+        }
+
+        let options = Options {
+            preserve_xattr: true,
+            ..Default::default()
+        };
+        assert!(copy_file(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap());
+        assert_eq!(
+            Some(b"red".to_vec()),
+            xattr::get(&target, "user.mediasort.tag").unwrap()
+        );
+    }
+
+    #[test]
+    fn move_removes_verified_source() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let target = tmpdir.path().join("moved/IMG_20210130_000001.jpg");
+        fs::write(&source, b"image").unwrap();
+
+        let options = Options {
+            move_files: true,
+            ..Default::default()
+        };
+        assert!(copy_file(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap());
+        assert!(!source.exists());
+        assert_eq!(b"image".to_vec(), fs::read(&target).unwrap());
+    }
+
+    #[test]
+    fn copy_overwrites_only_different_sizes() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let target = tmpdir.path().join("sorted/IMG_20210130_000001.jpg");
+        fs::write(&source, b"new").unwrap();
+        create_dir(target.parent().unwrap().to_str().unwrap()).unwrap();
+        fs::write(&target, b"old").unwrap();
+
+        let options = Options {
+            on_conflict: ConflictPolicy::OverwriteIfDifferentSize,
+            ..Default::default()
+        };
+        let (from, to) = (source.to_str().unwrap(), target.to_str().unwrap());
+        assert!(!copy_file(from, to, &options).unwrap());
+        assert_eq!(b"old".to_vec(), fs::read(&target).unwrap());
+
+        fs::write(&source, b"re-exported").unwrap();
+        assert!(copy_file(from, to, &options).unwrap());
+        assert_eq!(b"re-exported".to_vec(), fs::read(&target).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_leaves_original_untouched() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let target = tmpdir.path().join("dated/IMG_20210130_000001.jpg");
+        fs::write(&source, b"image").unwrap();
+
+        let options = Options {
+            symlink: true,
+            ..Default::default()
+        };
+        assert!(copy_file(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap());
+        assert!(fs::symlink_metadata(&target)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::read_link(&target).unwrap().is_absolute());
+        assert!(source.exists());
+    }
+
+    #[cfg(feature = "catalog")]
+    #[test]
+    fn catalog_records_dates_independent_of_layout() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+        let database = tmpdir.path().join("catalog.db");
+
+        let mut mediaconfig = MediaConfig::new(
+            source.to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        mediaconfig.options.layout = Some(Layout::Plex);
+        mediaconfig.options.catalog = Some(database.clone());
+        assert_eq!(1, mediaconfig.copy_media_files().unwrap().copied);
+
+        let connection = rusqlite::Connection::open(database).unwrap();
+        let (date, date_source): (String, String) = connection
+            .query_row("SELECT date, date_source FROM media", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!("2021-01-30", date);
+        assert_eq!("smartphone_name", date_source);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cas_stores_identical_content_once() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::write(source.join("IMG_20210130_000001.jpg"), b"image").unwrap();
+        fs::write(source.join("IMG_20210130_000002.jpg"), b"image").unwrap();
+        fs::write(source.join("IMG_20210131_000001.jpg"), b"other").unwrap();
+        let store = tmpdir.path().join("store");
+
+        let mut mediaconfig = MediaConfig::new(
+            source.to_str().unwrap().to_string(),
+            tmpdir.path().join("dated"),
+        );
+        mediaconfig.options.cas = Some(store.clone());
+        assert_eq!(3, mediaconfig.copy_media_files().unwrap().copied);
+
+        let first = tmpdir
+            .path()
+            .join("dated/2021/01/30/IMG_20210130_000001.jpg");
+        let second = tmpdir
+            .path()
+            .join("dated/2021/01/30/IMG_20210130_000002.jpg");
+        assert!(fs::symlink_metadata(&first).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_link(&first).unwrap(),
+            fs::read_link(&second).unwrap()
+        );
+        assert!(fs::read_link(&first).unwrap().starts_with(&store));
+        assert_eq!(b"image".to_vec(), fs::read(&second).unwrap());
+        let shards = fs::read_dir(&store).unwrap().count();
+        assert_eq!(2, shards);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_attributes_keeps_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("IMG_20210130_000001.jpg");
+        let target = tmpdir.path().join("copy/IMG_20210130_000001.jpg");
+        fs::write(&source, b"image").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let options = Options {
+            copy_attributes: true,
+            ..Default::default()
+        };
+        assert!(copy_file(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap());
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(0o640, mode & 0o777);
+    }
+
+    #[test]
+    fn verify_size_removes_truncated_copy() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source.jpg");
+        let target = tmpdir.path().join("target.jpg");
+        fs::write(&source, b"complete image").unwrap();
+        fs::write(&target, b"complete").unwrap();
+
+        assert!(verify_size(source.to_str().unwrap(), target.to_str().unwrap()).is_err());
+        assert!(!target.exists());
+
+        fs::copy(&source, &target).unwrap();
+        assert!(verify_size(source.to_str().unwrap(), target.to_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn target_from_environment() {
+        env::set_var(TARGET_ENV, "/mnt/photos");
+        assert_eq!(PathBuf::from("/mnt/photos"), resolve_target(None).unwrap());
+        assert_eq!(
+            PathBuf::from("/cli/target"),
+            resolve_target(Some(PathBuf::from("/cli/target"))).unwrap()
+        );
+        env::remove_var(TARGET_ENV);
+        assert!(resolve_target(None).unwrap().ends_with("Pictures"));
+    }
+
+    #[test]
+    fn dry_run_forced_by_environment() {
+        env::set_var(DRY_RUN_ENV, "1");
+        assert!(dry_run_forced());
+        env::set_var(DRY_RUN_ENV, "0");
+        assert!(!dry_run_forced());
+        env::remove_var(DRY_RUN_ENV);
+        assert!(!dry_run_forced());
+    }
+
+    #[test]
+    fn test_is_locked() {
+        let locked: Box<dyn Error> = if cfg!(windows) {
+            Box::new(io::Error::from_raw_os_error(32))
+        } else {
+            Box::new(io::Error::from(io::ErrorKind::ResourceBusy))
+        };
+        let missing: Box<dyn Error> = Box::new(io::Error::from(io::ErrorKind::NotFound));
+        let other: Box<dyn Error> = String::from("not an io error").into();
+        assert!(is_locked(locked.as_ref()));
+        assert!(!is_locked(missing.as_ref()));
+        assert!(!is_locked(other.as_ref()));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_path_gets_extended_prefix() {
+        let long = format!(r"C:\{}\file.jpg", "x".repeat(300));
+        assert!(long_path(Path::new(&long))
+            .to_str()
+            .unwrap()
+            .starts_with(r"\\?\C:\"));
+        assert_eq!(
+            PathBuf::from(r"C:\short\file.jpg"),
+            long_path(Path::new(r"C:\short\file.jpg"))
+        );
+    }
+
+    #[test]
+    fn identical_targets_are_skipped_and_different_ones_renamed() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let folder = tmpdir.path().join("target/2021/01/30");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&folder).unwrap();
+        for (name, content, existing) in [
+            ("IMG_20210130_000001.jpg", "image", "image"),
+            ("IMG_20210130_000002.jpg", "other", "image"),
+            ("IMG_20210130_000003.jpg", "large image", "image"),
+        ] {
+            fs::write(source.join(name), content).unwrap();
+            fs::write(folder.join(name), existing).unwrap();
+        }
+
+        let mut mediaconfig = MediaConfig::new(
+            source.to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        let summary = mediaconfig.copy_media_files().unwrap();
+        assert_eq!((2, 1), (summary.copied, summary.skipped));
+        assert!(!folder.join("IMG_20210130_000001_2.jpg").exists());
+        for name in ["IMG_20210130_000002", "IMG_20210130_000003"] {
+            assert_eq!(
+                "image",
+                fs::read_to_string(folder.join(format!("{}.jpg", name))).unwrap()
+            );
+            assert_eq!(
+                fs::read(source.join(format!("{}.jpg", name))).unwrap(),
+                fs::read(folder.join(format!("{}_2.jpg", name))).unwrap()
+            );
+        }
+
+        // a re-run finds the renamed copies instead of renaming them again
+        for _ in 0..2 {
+            let summary = mediaconfig.copy_media_files().unwrap();
+            assert_eq!((0, 3), (summary.copied, summary.skipped));
+        }
+        assert_eq!(5, fs::read_dir(&folder).unwrap().count());
+
+        // overwriting leaves identical files alone as well
+        mediaconfig.options.on_conflict = ConflictPolicy::Overwrite;
+        let summary = mediaconfig.copy_media_files().unwrap();
+        assert_eq!((2, 1), (summary.copied, summary.skipped));
+        assert_eq!(
+            "other",
+            fs::read_to_string(folder.join("IMG_20210130_000002.jpg")).unwrap()
+        );
+    }
+
+    #[test]
+    fn rename_on_conflict_keeps_both_files() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        for folder in ["a", "b"] {
+            create_dir(source.join(folder).to_str().unwrap()).unwrap();
+            fs::write(source.join(folder).join("IMG_20210130_000001.jpg"), folder).unwrap();
+        }
+        let target = tmpdir.path().join("target");
+        let log = tmpdir.path().join("collisions.log");
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.on_conflict = ConflictPolicy::Rename;
+        mediaconfig.options.collision_log = Some(log.clone());
+        mediaconfig.options.dry_run = true;
+        mediaconfig.copy_media_files().unwrap();
+        assert_eq!("", fs::read_to_string(&log).unwrap_or_default());
+
+        mediaconfig.options.dry_run = false;
+        let summary = mediaconfig.copy_media_files().unwrap();
+        assert_eq!(
+            (2, 2, 0, 0, 2),
+            (
+                summary.found,
+                summary.copied,
+                summary.skipped,
+                summary.errored,
+                summary.bytes
+            )
+        );
+
+        let folder = target.join("2021/01/30");
+        assert_eq!(
+            "a",
+            fs::read_to_string(folder.join("IMG_20210130_000001.jpg")).unwrap()
+        );
+        assert_eq!(
+            "b",
+            fs::read_to_string(folder.join("IMG_20210130_000001_2.jpg")).unwrap()
+        );
+        assert!(fs::read_to_string(log)
+            .unwrap()
+            .contains("b/IMG_20210130_000001.jpg\t"));
+    }
+
+    #[test]
+    fn videos_go_to_video_target() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+        fs::File::create(source.join("VID_20210130_000002.mp4")).unwrap();
+        let photos = tmpdir.path().join("Photos");
+        let videos = tmpdir.path().join("Videos");
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), photos.clone());
+        mediaconfig.options.video_target = Some(videos.clone());
+        mediaconfig.copy_media_files().unwrap();
+
+        assert!(photos.join("2021/01/30/IMG_20210130_000001.jpg").exists());
+        assert!(!photos.join("2021/01/30/VID_20210130_000002.mp4").exists());
+        assert!(videos.join("2021/01/30/VID_20210130_000002.mp4").exists());
+        assert!(!videos.join("2021/01/30/IMG_20210130_000001.jpg").exists());
+    }
+
+    #[test]
+    fn corrupt_files_are_quarantined() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        create_dir(source.to_str().unwrap()).unwrap();
+        // the exif segment announces more bytes than the file contains
+        fs::write(
+            source.join("truncated.jpg"),
+            b"\xff\xd8\xff\xe1\x10\x00Exif\0\0MM",
+        )
+        .unwrap();
+        fs::write(source.join("no_exif.jpg"), b"\xff\xd8\xff\xd9").unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            source.to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        mediaconfig.options.quarantine_corrupt = true;
+        mediaconfig.find_all_media_files(None, true).unwrap();
+
+        let truncated = source.join("truncated.jpg");
+        let no_exif = source.join("no_exif.jpg");
+        assert_eq!(
+            "corrupt/truncated.jpg",
+            mediaconfig.files[truncated.to_str().unwrap()].target
+        );
+        assert!(!mediaconfig.files[no_exif.to_str().unwrap()]
+            .target
+            .starts_with("corrupt/"));
+    }
+
+    #[test]
+    fn category_targets_route_by_extension_and_category() {
+        let mut mediaconfig = MediaConfig::new(String::from("/src"), PathBuf::from("/photos"));
+        mediaconfig.options.video_target = Some(PathBuf::from("/videos"));
+        mediaconfig.options.category_targets = vec![
+            parse_category_target("raw=/mnt/raw").unwrap(),
+            parse_category_target(".PNG=/mnt/png").unwrap(),
+            parse_category_target("mov=/mnt/mov").unwrap(),
+        ];
+        let undated = MediaEntry {
+            target: String::from("a"),
+            date_source: None,
+            date: None,
+        };
+        let root = |source| mediaconfig.target_root(source, &undated);
+
+        assert_eq!(Path::new("/mnt/raw"), root("/src/a.NEF"));
+        assert_eq!(Path::new("/mnt/raw"), root("/src/a.dng"));
+        assert_eq!(Path::new("/mnt/png"), root("/src/a.png"));
+        assert_eq!(Path::new("/mnt/mov"), root("/src/a.mov"));
+        assert_eq!(Path::new("/videos"), root("/src/a.mp4"));
+        assert_eq!(Path::new("/photos"), root("/src/a.jpg"));
+        assert!(parse_category_target("docs=/mnt/docs").is_err());
+        assert!(parse_category_target("raw").is_err());
+    }
+
+    #[test]
+    fn old_files_go_to_archive_target() {
+        let mut mediaconfig = MediaConfig::new(String::from("/src"), PathBuf::from("/photos"));
+        mediaconfig.options.video_target = Some(PathBuf::from("/videos"));
+        mediaconfig.options.archive_before = Some(parse_archive_before("2024-01-01").unwrap());
+        mediaconfig.options.archive_target = Some(PathBuf::from("/archive"));
+
+        let root = |source: &str| {
+            let entry = dated_path(source, &mediaconfig.options).unwrap();
+            mediaconfig.target_root(source, &entry).to_path_buf()
+        };
+        assert_eq!(Path::new("/archive"), root("/src/IMG_20231231_235959.jpg"));
+        assert_eq!(Path::new("/photos"), root("/src/IMG_20240101_000000.jpg"));
+        assert_eq!(Path::new("/archive"), root("/src/VID_20231231_235959.mp4"));
+        assert_eq!(Path::new("/videos"), root("/src/VID_20240101_000000.mp4"));
+        // a year alone counts as its last day
+        assert_eq!(Path::new("/archive"), root("/src/2023/a.jpg"));
+        assert_eq!(Path::new("/photos"), root("/src/2024/a.jpg"));
+        // the date the file was sorted by decides, it is not looked up again
+        let entry = MediaEntry::dated(
+            String::from("2023/12/31/IMG_20240101_000000.jpg"),
+            DateSource::Exif,
+        );
+        assert_eq!(
+            Path::new("/archive"),
+            mediaconfig.target_root("/src/IMG_20240101_000000.jpg", &entry)
+        );
+    }
+
+    #[test]
+    fn archive_before_accepts_dates_and_months() {
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 1, 1),
+            parse_archive_before("2024-01-01").ok()
+        );
+        let today = Local::now().date_naive();
+        assert_eq!(
+            today.checked_sub_months(Months::new(12)),
+            parse_archive_before("12m").ok()
+        );
+        assert!(parse_archive_before("twelve").is_err());
+        assert!(parse_archive_before("xm").is_err());
+    }
+
+    #[test]
+    fn suggest_config_samples_files() {
+        let tmpdir = TempDir::new().unwrap();
+        fs::copy(
+            test_case!("test_image.JPG"),
+            tmpdir.path().join("test_image.JPG"),
+        )
+        .unwrap();
+        fs::File::create(tmpdir.path().join("IMG_20210130_000001.jpg")).unwrap();
+        fs::File::create(tmpdir.path().join("IMG_20210130_000002.jpg")).unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        let suggestion = mediaconfig.suggest_config(2).unwrap();
+        assert!(suggestion.starts_with("Sampled 2 files\n  filename   100%\n"));
+        assert!(suggestion.contains("suggest --strategy-order filename,mtime"));
+        assert!(!tmpdir.path().join("target").exists());
+    }
+
+    #[test]
+    fn exif_csv_lists_camera_and_strategy() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::copy(test_case!("test_image.JPG"), source.join("test_image.JPG")).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+        let csv = tmpdir.path().join("exif.csv");
+
+        let mut mediaconfig = MediaConfig::new(
+            source.to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        mediaconfig.write_exif_csv(&csv).unwrap();
+
+        let csv = fs::read_to_string(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!("path,date,make,model,strategy", lines[0]);
+        assert!(lines[1].ends_with("IMG_20210130_000001.jpg,2021-01-30,,,smartphone_name"));
+        assert!(
+            lines[2].ends_with("test_image.JPG,2022-12-17,OLYMPUS CORPORATION,E-M10MarkII,exif")
+        );
+        assert!(!tmpdir.path().join("target").exists());
+
+        // the full date, whatever folders the layout sorts into
+        for (layout, granularity) in [
+            (Some(Layout::Plex), Granularity::Day),
+            (None, Granularity::Month),
+        ] {
+            let csv = tmpdir.path().join("layout.csv");
+            let mut mediaconfig = MediaConfig::new(
+                source.to_str().unwrap().to_string(),
+                tmpdir.path().join("target"),
+            );
+            mediaconfig.options.layout = layout;
+            mediaconfig.options.granularity = granularity;
+            mediaconfig.write_exif_csv(&csv).unwrap();
+            let csv = fs::read_to_string(csv).unwrap();
+            assert!(
+                csv.contains("IMG_20210130_000001.jpg,2021-01-30,"),
+                "{}",
+                csv
+            );
+        }
+    }
+
+    #[test]
+    fn new_files_follow_existing_layout() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        create_dir(source.to_str().unwrap()).unwrap();
+        create_dir(target.join("2019/07").to_str().unwrap()).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.merge_into_existing_structure = true;
+        mediaconfig.copy_media_files().unwrap();
+
+        assert_eq!(Granularity::Month, mediaconfig.options.granularity);
+        assert!(target.join("2021/01/IMG_20210130_000001.jpg").exists());
+    }
+
+    #[test]
+    fn files_at_their_target_are_left_alone() {
+        let tmpdir = TempDir::new().unwrap();
+        let folder = tmpdir.path().join("2021/01/30");
+        create_dir(folder.to_str().unwrap()).unwrap();
+        fs::write(folder.join("IMG_20210130_000001.jpg"), b"image").unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().to_path_buf(),
+        );
+        mediaconfig.options.on_conflict = ConflictPolicy::Rename;
+        let summary = mediaconfig.copy_media_files().unwrap();
+
+        assert_eq!(
+            (1, 0, 0, 1),
+            (
+                summary.found,
+                summary.copied,
+                summary.skipped,
+                summary.in_place
+            )
+        );
+        assert_eq!(
+            vec!["IMG_20210130_000001.jpg"],
+            fs::read_dir(&folder)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn dng_date_from_xmp_packet() {
+        let tmpdir = TempDir::new().unwrap();
+        let raw = tmpdir.path().join("raw.dng");
+        let mut data = b"II*\x00\x08\x00\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(
+            br#"<x:xmpmeta><rdf:Description xmp:CreateDate="2020-02-29T08:00:00"/></x:xmpmeta>"#,
+        );
+        fs::write(&raw, data).unwrap();
+        assert_eq!(
+            Some("2020/02/29/raw.dng".to_string()),
+            read_exif(raw.to_str().unwrap(), false)
+        );
+
+        fs::write(&raw, b"II*\x00\x08\x00\x00\x00\x00\x00").unwrap();
+        assert_eq!(None, read_exif(raw.to_str().unwrap(), false));
+    }
+
+    #[test]
+    fn stop_before_copying() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.stop_handle().store(true, Ordering::SeqCst);
+        let summary = mediaconfig.copy_media_files().unwrap();
+
+        assert!(summary.interrupted);
+        assert_eq!((1, 0), (summary.found, summary.copied));
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn sequence_numbers_count_by_scope() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        for (event, name) in [
+            ("wedding", "IMG_20210131_090000.jpg"),
+            ("trip", "IMG_20210130_180000.jpg"),
+            ("wedding", "IMG_20210130_120000.jpg"),
+        ] {
+            fs::create_dir_all(source.join(event)).unwrap();
+            fs::write(source.join(event).join(name), name).unwrap();
+        }
+        let added = source.join("wedding/IMG_20210130_060000.jpg");
+
+        for (scope, expected, next) in [
+            (
+                SequenceScope::Day,
+                [
+                    "0001_IMG_20210130_120000.jpg",
+                    "0001_IMG_20210131_090000.jpg",
+                    "0002_IMG_20210130_180000.jpg",
+                ],
+                "0003_IMG_20210130_060000.jpg",
+            ),
+            (
+                SequenceScope::Folder,
+                [
+                    "0001_IMG_20210130_120000.jpg",
+                    "0002_IMG_20210130_180000.jpg",
+                    "0003_IMG_20210131_090000.jpg",
+                ],
+                "0004_IMG_20210130_060000.jpg",
+            ),
+            (
+                SequenceScope::Event,
+                [
+                    "0001_IMG_20210130_120000.jpg",
+                    "0001_IMG_20210130_180000.jpg",
+                    "0002_IMG_20210131_090000.jpg",
+                ],
+                "0003_IMG_20210130_060000.jpg",
+            ),
+        ] {
+            let _ = fs::remove_file(&added);
+            let target = tmpdir.path().join(format!("{:?}", scope));
+            let run = || {
+                let mut mediaconfig =
+                    MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+                mediaconfig.options.recursive = true;
+                mediaconfig.options.granularity = Granularity::Month;
+                mediaconfig.options.sequence_scope = Some(scope);
+                let summary = mediaconfig.copy_media_files().unwrap();
+                (summary.copied, summary.skipped)
+            };
+            assert_eq!((3, 0), run());
+            let names = || {
+                let mut names: Vec<String> = fs::read_dir(target.join("2021/01"))
+                    .unwrap()
+                    .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                    .collect();
+                names.sort();
+                names
+            };
+            assert_eq!(expected.to_vec(), names(), "{:?}", scope);
+
+            // the copied files keep their numbers and a new one continues after them
+            assert_eq!((0, 3), run());
+            fs::write(&added, "added").unwrap();
+            assert_eq!((1, 3), run());
+            assert_eq!(4, names().len());
+            assert!(names().contains(&next.to_string()), "{:?}", scope);
+        }
+    }
+
+    #[test]
+    fn dedup_identical_copies_one_of_identical_sources() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        for (folder, name, content) in [
+            ("backup", "IMG_20210130_000001.jpg", "picture"),
+            ("phone", "IMG_20210130_000001.jpg", "picture"),
+            ("phone", "IMG_20210130_000002.jpg", "picture"),
+            ("export", "IMG_20210130_000001.jpg", "small"),
+        ] {
+            create_dir(source.join(folder).to_str().unwrap()).unwrap();
+            fs::write(source.join(folder).join(name), content).unwrap();
+        }
+        let target = tmpdir.path().join("target");
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.dedup_identical = true;
+        let summary = mediaconfig.copy_media_files().unwrap();
+
+        // one of the identical pictures, and the export with the same name
+        // but other content, renamed by the conflict policy
+        assert_eq!((2, 2), (summary.copied, summary.skipped));
+        let folder = target.join("2021/01/30");
+        assert_eq!(
+            "picture",
+            fs::read_to_string(folder.join("IMG_20210130_000001.jpg")).unwrap()
+        );
+        assert_eq!(
+            "small",
+            fs::read_to_string(folder.join("IMG_20210130_000001_2.jpg")).unwrap()
+        );
+        assert!(!folder.join("IMG_20210130_000002.jpg").exists());
+    }
+
+    #[test]
+    fn raw_date_from_sidecar() {
+        let tmpdir = TempDir::new().unwrap();
+        let raw = tmpdir.path().join("DSC_0001.nef");
+        fs::write(&raw, b"raw").unwrap();
+        fs::write(
+            tmpdir.path().join("DSC_0001.nef.xmp"),
+            b"<xmp:CreateDate>2018-03-01T09:00:00</xmp:CreateDate>",
+        )
+        .unwrap();
+        assert_eq!(
+            Some("2018/03/01/DSC_0001.nef".to_string()),
+            read_exif(raw.to_str().unwrap(), false)
+        );
+    }
+
+    #[test]
+    fn sidecars_follow_copied_files_only() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        let folder = target.join("2021/01/30");
+        create_dir(source.to_str().unwrap()).unwrap();
+        create_dir(folder.to_str().unwrap()).unwrap();
+        for name in ["IMG_20210130_000001", "IMG_20210130_000002"] {
+            fs::write(source.join(format!("{}.jpg", name)), b"image").unwrap();
+            fs::write(source.join(format!("{}.xmp", name)), b"new edit").unwrap();
+        }
+        fs::write(folder.join("IMG_20210130_000002.jpg"), b"image").unwrap();
+        fs::write(folder.join("IMG_20210130_000002.xmp"), b"old edit").unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.sidecars = true;
+        mediaconfig.copy_media_files().unwrap();
+        assert!(folder.join("IMG_20210130_000001.xmp").exists());
+        assert_eq!(
+            "old edit",
+            fs::read_to_string(folder.join("IMG_20210130_000002.xmp")).unwrap()
+        );
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.sidecars = true;
+        mediaconfig.options.sync_sidecars = true;
+        mediaconfig.copy_media_files().unwrap();
+        assert_eq!(
+            "new edit",
+            fs::read_to_string(folder.join("IMG_20210130_000002.xmp")).unwrap()
+        );
+    }
+
+    #[test]
+    fn chunked_copy_resumes_after_cursor() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        let cursor = tmpdir.path().join("cursor");
+        for (folder, name) in [
+            ("a", "IMG_20210130_000001.jpg"),
+            ("b", "IMG_20210131_000001.jpg"),
+        ] {
+            create_dir(source.join(folder).to_str().unwrap()).unwrap();
+            fs::File::create(source.join(folder).join(name)).unwrap();
+        }
+        fs::write(&cursor, source.join("a").to_str().unwrap()).unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.resume_cursor = Some(cursor.clone());
+        let summary = mediaconfig.copy_media_files().unwrap();
+
+        assert_eq!((1, 1), (summary.found, summary.copied));
+        assert!(!target.join("2021/01/30/IMG_20210130_000001.jpg").exists());
+        assert!(target.join("2021/01/31/IMG_20210131_000001.jpg").exists());
+        assert!(!cursor.exists());
+    }
+
+    #[test]
+    fn exif_date_is_written_into_the_copy_only() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        create_dir(source.to_str().unwrap()).unwrap();
+        let jpeg = [0xff, 0xd8, 0xff, 0xd9];
+        fs::write(source.join("IMG_20210130_142501.jpg"), jpeg).unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.write_exif_date = true;
+        mediaconfig.copy_media_files().unwrap();
+
+        assert_eq!(
+            jpeg.to_vec(),
+            fs::read(source.join("IMG_20210130_142501.jpg")).unwrap()
+        );
+        let copy = target.join("2021/01/30/IMG_20210130_142501.jpg");
+        let exif = open_exif(copy.to_str().unwrap(), false).unwrap();
+        let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).unwrap();
+        assert_eq!("2021-01-30 14:25:01", field.display_value().to_string());
+
+        // the added date is expected when verifying the copy
+        fs::remove_file(&copy).unwrap();
+        mediaconfig.options.verify_all = true;
+        let summary = mediaconfig.copy_media_files().unwrap();
+        assert_eq!((1, Some(0)), (summary.copied, summary.verify_mismatches));
+
+        // a move only removes the source once the date is written
+        let copy_content = fs::read(&copy).unwrap();
+        fs::remove_file(&copy).unwrap();
+        mediaconfig.options.verify_all = false;
+        mediaconfig.options.move_files = true;
+        mediaconfig.options.atomic = true;
+        assert_eq!(1, mediaconfig.copy_media_files().unwrap().copied);
+        assert!(!source.join("IMG_20210130_142501.jpg").exists());
+        assert_eq!(copy_content, fs::read(&copy).unwrap());
+        assert_eq!(1, fs::read_dir(copy.parent().unwrap()).unwrap().count());
+    }
+
+    #[test]
+    fn batched_copy_counts_every_batch() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        let target = tmpdir.path().join("target");
+        for (folder, name) in [
+            ("a", "IMG_20210130_000001.jpg"),
+            ("a", "IMG_20210130_000002.jpg"),
+            ("a", "IMG_20210130_000003.jpg"),
+            ("b", "IMG_20210131_000001.jpg"),
+            ("b", "IMG_20210131_000002.jpg"),
+        ] {
+            create_dir(source.join(folder).to_str().unwrap()).unwrap();
+            fs::File::create(source.join(folder).join(name)).unwrap();
+        }
+        fs::create_dir_all(target.join("2021/01/31")).unwrap();
+        fs::File::create(target.join("2021/01/31/IMG_20210131_000002.jpg")).unwrap();
+
+        let mut mediaconfig =
+            MediaConfig::new(source.to_str().unwrap().to_string(), target.clone());
+        mediaconfig.options.recursive = true;
+        mediaconfig.options.max_files_in_memory = Some(2);
+        let summary = mediaconfig.copy_media_files().unwrap();
+
+        assert_eq!((5, 4, 1), (summary.found, summary.copied, summary.skipped));
+        assert!(target.join("2021/01/30/IMG_20210130_000003.jpg").exists());
+        assert!(target.join("2021/01/31/IMG_20210131_000001.jpg").exists());
+        assert!(mediaconfig.files.len() <= 2);
+    }
+
+    #[test]
+    fn in_place_rename_keeps_folders() {
+        let tmpdir = TempDir::new().unwrap();
+        let folder = tmpdir.path().join("Holiday");
+        create_dir(folder.to_str().unwrap()).unwrap();
+        fs::write(folder.join("IMG_20210130_142501.jpg"), b"a").unwrap();
+        fs::write(folder.join("VID_20210130_142501.mp4"), b"b").unwrap();
+        fs::write(folder.join("20210130_142501.jpg"), b"c").unwrap();
+
+        let mut mediaconfig = MediaConfig::new(
+            tmpdir.path().to_str().unwrap().to_string(),
+            tmpdir.path().join("unused"),
+        );
+        mediaconfig.options.in_place_rename = true;
+        mediaconfig.options.dry_run = true;
+        mediaconfig.copy_media_files().unwrap();
+        assert!(folder.join("IMG_20210130_142501.jpg").exists());
+
+        mediaconfig.options.dry_run = false;
+        mediaconfig.options.on_conflict = ConflictPolicy::Rename;
+        let summary = mediaconfig.copy_media_files().unwrap();
+        assert_eq!((3, 2, 1), (summary.found, summary.copied, summary.in_place));
+        assert_eq!(
+            b"c".to_vec(),
+            fs::read(folder.join("20210130_142501.jpg")).unwrap()
+        );
+        assert_eq!(
+            b"b".to_vec(),
+            fs::read(folder.join("20210130_142501.mp4")).unwrap()
+        );
+        assert_eq!(
+            b"a".to_vec(),
+            fs::read(folder.join("20210130_142501_2.jpg")).unwrap()
+        );
+        assert!(!tmpdir.path().join("unused").exists());
+    }
+
+    #[test]
+    fn dry_run_copies_nothing_and_writes_report() {
+        let tmpdir = TempDir::new().unwrap();
+        let source = tmpdir.path().join("source");
+        create_dir(source.to_str().unwrap()).unwrap();
+        fs::File::create(source.join("IMG_20210130_000001.jpg")).unwrap();
+        let report = tmpdir.path().join("report.json");
+
+        let mut mediaconfig = MediaConfig::new(
+            source.to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        mediaconfig.options.dry_run = true;
+        mediaconfig.options.report = Some(report.clone());
+        mediaconfig.copy_media_files().unwrap();
+
+        assert!(!tmpdir.path().join("target").exists());
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(report).unwrap()).unwrap();
+        assert_eq!(true, json["dry_run"]);
+        assert_eq!("planned", json["files"][0]["status"]);
+        assert_eq!("smartphone_name", json["files"][0]["date_source"]);
+        assert!(json["files"][0]["target"]
+            .as_str()
+            .unwrap()
+            .ends_with("2021/01/30/IMG_20210130_000001.jpg"));
+    }
+
+    #[test]
+    fn staged_sources_are_recorded_under_their_label() {
+        let tmpdir = TempDir::new().unwrap();
+        let staging = tmpdir.path().join("staging");
+        create_dir(staging.join("DCIM").to_str().unwrap()).unwrap();
+        fs::File::create(staging.join("DCIM/IMG_20210130_000001.jpg")).unwrap();
+        let report = tmpdir.path().join("report.json");
+
+        let mut mediaconfig = MediaConfig::new(
+            staging.to_str().unwrap().to_string(),
+            tmpdir.path().join("target"),
+        );
+        mediaconfig.options.source_label = Some(String::from("/backups/phone.zip"));
+        mediaconfig.options.report = Some(report.clone());
+        mediaconfig.options.write_index = true;
+        mediaconfig.copy_media_files().unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(report).unwrap()).unwrap();
+        assert_eq!(
+            "/backups/phone.zip/DCIM/IMG_20210130_000001.jpg",
+            json["files"][0]["source"]
+        );
+        let index = index::FolderIndex::load(&tmpdir.path().join("target/2021/01/30"));
+        assert_eq!(
+            "/backups/phone.zip/DCIM/IMG_20210130_000001.jpg",
+            index.files["IMG_20210130_000001.jpg"].source
+        );
     }
 }
@@ -0,0 +1,89 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+// Link the target to its source instead of copying it. Links point to the
+// absolute source by default, which survives moving the link farm around,
+// or relative to the link, which survives moving both together.
+pub fn link_file(source: &Path, target: &Path, relative: bool) -> io::Result<()> {
+    let source = fs::canonicalize(source)?;
+    let destination = if relative {
+        let folder = target
+            .parent()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "link has no folder"))?;
+        relative_path(&fs::canonicalize(folder)?, &source)
+    } else {
+        source
+    };
+    symlink(&destination, target)
+}
+
+// The path leading from a folder to a file, both absolute
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    path
+}
+
+#[cfg(unix)]
+fn symlink(destination: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(destination, link)
+}
+
+#[cfg(windows)]
+fn symlink(destination: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(destination, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            PathBuf::from("../../library/a/IMG_1.jpg"),
+            relative_path(
+                Path::new("/data/sorted/2021"),
+                Path::new("/data/library/a/IMG_1.jpg")
+            )
+        );
+        assert_eq!(
+            PathBuf::from("IMG_1.jpg"),
+            relative_path(Path::new("/data"), Path::new("/data/IMG_1.jpg"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_point_to_the_source() {
+        let tmpdir = TempDir::new().unwrap();
+        let root = fs::canonicalize(tmpdir.path()).unwrap();
+        let source = root.join("library/IMG_1.jpg");
+        let folder = root.join("sorted/2021/01/30");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(&source, b"image").unwrap();
+
+        let absolute = folder.join("absolute.jpg");
+        link_file(&source, &absolute, false).unwrap();
+        assert_eq!(source, fs::read_link(&absolute).unwrap());
+
+        let relative = folder.join("relative.jpg");
+        link_file(&source, &relative, true).unwrap();
+        assert_eq!(
+            PathBuf::from("../../../../library/IMG_1.jpg"),
+            fs::read_link(&relative).unwrap()
+        );
+        assert_eq!(b"image".to_vec(), fs::read(&relative).unwrap());
+    }
+}
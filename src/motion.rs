@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::{is_video_file, DateSource, MediaEntry};
+
+// How much of a file to search for the motion photo XMP markers
+const XMP_SEARCH_LIMIT: u64 = 256 * 1024;
+
+// Whether an image embeds a short video, like Android MVIMG or Google motion photos.
+// This is best-effort, it looks for the markers in the XMP packet near the start.
+pub fn is_motion_photo(path: &Path) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::new();
+    if file.take(XMP_SEARCH_LIMIT).read_to_end(&mut head).is_err() {
+        return false;
+    }
+    [&b"GCamera:MotionPhoto"[..], b"GCamera:MicroVideo"]
+        .iter()
+        .any(|marker| head.windows(marker.len()).any(|window| window == *marker))
+}
+
+// The folder and date of a still, which its Live Photo video takes over
+struct Still {
+    folder: String,
+    date: Option<String>,
+    date_source: Option<DateSource>,
+}
+
+// Move the video half of a Live Photo (IMG_1234.HEIC + IMG_1234.MOV) into the
+// folder of its still image, so both parts of the pair stay together. The
+// video is dated like the still, which is where it goes.
+pub fn pair_live_photos(files: &mut HashMap<String, MediaEntry>) {
+    let mut stills = HashMap::new();
+    // sorted, so the same still wins every run if several share a stem
+    let mut sources: Vec<&String> = files.keys().collect();
+    sources.sort();
+    for source in sources.into_iter().rev() {
+        let entry = &files[source];
+        let path = Path::new(source);
+        if !is_video_file(path) {
+            if let (Some(key), Some((folder, _))) = (pair_key(path), entry.target.rsplit_once('/'))
+            {
+                let still = Still {
+                    folder: folder.to_string(),
+                    date: entry.date.clone(),
+                    date_source: entry.date_source,
+                };
+                stills.insert(key, still);
+            }
+        }
+    }
+    for (source, entry) in files.iter_mut() {
+        let path = Path::new(source);
+        if !is_video_file(path) {
+            continue;
+        }
+        let (Some(key), Some((_, name))) = (pair_key(path), entry.target.rsplit_once('/')) else {
+            continue;
+        };
+        if let Some(still) = stills.get(&key) {
+            debug!("Keeping Live Photo video {} with its still", source);
+            entry.target = format!("{}/{}", still.folder, name);
+            entry.date = still.date.clone();
+            entry.date_source = still.date_source;
+        }
+    }
+}
+
+// Directory and case-insensitive stem, which are shared by both files of a pair
+fn pair_key(path: &Path) -> Option<(String, String)> {
+    Some((
+        path.parent()?.to_string_lossy().into_owned(),
+        path.file_stem()?.to_string_lossy().to_lowercase(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn live_photo_video_follows_still() {
+        let mut files = HashMap::from([
+            (
+                String::from("/phone/IMG_1234.HEIC"),
+                MediaEntry::dated(String::from("2022/06/15/IMG_1234.HEIC"), DateSource::Exif),
+            ),
+            (
+                String::from("/phone/IMG_1234.MOV"),
+                MediaEntry::dated(
+                    String::from("2022/06/16/IMG_1234.MOV"),
+                    DateSource::VideoMetadata,
+                ),
+            ),
+            (
+                String::from("/phone/IMG_9999.MOV"),
+                MediaEntry::dated(
+                    String::from("2022/06/16/IMG_9999.MOV"),
+                    DateSource::VideoMetadata,
+                ),
+            ),
+        ]);
+        pair_live_photos(&mut files);
+
+        assert_eq!(
+            "2022/06/15/IMG_1234.MOV",
+            files["/phone/IMG_1234.MOV"].target
+        );
+        assert_eq!(
+            Some("2022/06/15"),
+            files["/phone/IMG_1234.MOV"].date.as_deref()
+        );
+        assert_eq!(
+            Some(DateSource::Exif),
+            files["/phone/IMG_1234.MOV"].date_source
+        );
+        assert_eq!(
+            "2022/06/16/IMG_9999.MOV",
+            files["/phone/IMG_9999.MOV"].target
+        );
+    }
+
+    #[test]
+    fn detect_motion_photo() {
+        let tmpdir = TempDir::new().unwrap();
+        let motion = tmpdir.path().join("MVIMG_20230115_102911.jpg");
+        let still = tmpdir.path().join("IMG_20230115_102911.jpg");
+        fs::write(&motion, b"\xff\xd8<x:xmpmeta GCamera:MotionPhoto=\"1\"/>").unwrap();
+        fs::write(&still, b"\xff\xd8<x:xmpmeta/>").unwrap();
+
+        assert!(is_motion_photo(&motion));
+        assert!(!is_motion_photo(&still));
+    }
+}
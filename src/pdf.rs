@@ -0,0 +1,101 @@
+use chrono::NaiveDate;
+use std::error::Error;
+
+// PDFs are only recognized as media in builds with the pdf feature
+pub fn is_supported() -> bool {
+    cfg!(feature = "pdf")
+}
+
+// The date of the /CreationDate in the document information of a PDF, as
+// written by document scanners. Only the metadata is read, not the pages.
+#[cfg(feature = "pdf")]
+pub fn read_pdf_date(filename: &str) -> Option<NaiveDate> {
+    let metadata = match lopdf::Document::load_metadata(filename) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            debug!("Could not read the PDF metadata of {}: {}", filename, e);
+            return None;
+        }
+    };
+    parse_pdf_date(metadata.creation_date.as_deref()?)
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn read_pdf_date(_filename: &str) -> Option<NaiveDate> {
+    None
+}
+
+// The day of a PDF date like D:20180309142501+01'00'. The time and offset are
+// optional and ignored, like EXIF dates the day is taken as written.
+#[cfg_attr(not(feature = "pdf"), allow(dead_code))]
+fn parse_pdf_date(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    let digits = value.strip_prefix("D:").unwrap_or(value);
+    let day = digits.get(..8)?;
+    if !day.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    NaiveDate::parse_from_str(day, "%Y%m%d").ok()
+}
+
+// Build a minimal PDF whose document information only has a creation date
+#[cfg(feature = "pdf")]
+pub fn pdf_with_creation_date(date: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    use lopdf::{dictionary, Document, Object};
+
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.add_object(dictionary! {
+        "Type" => "Pages",
+        "Kids" => Vec::<Object>::new(),
+        "Count" => 0,
+    });
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    let info_id = document.add_object(dictionary! {
+        "CreationDate" => Object::string_literal(date),
+    });
+    document.trailer.set("Root", catalog_id);
+    document.trailer.set("Info", info_id);
+    let mut pdf = Vec::new();
+    document.save_to(&mut pdf)?;
+    Ok(pdf)
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn pdf_with_creation_date(_date: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err("PDF samples need a build with the pdf feature".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdf_dates_are_parsed() {
+        let date = NaiveDate::from_ymd_opt(2018, 3, 9);
+        assert_eq!(date, parse_pdf_date("D:20180309142501"));
+        assert_eq!(date, parse_pdf_date("D:20180309142501+01'00'"));
+        assert_eq!(date, parse_pdf_date("20180309"));
+        assert_eq!(None, parse_pdf_date("D:2018"));
+        assert_eq!(None, parse_pdf_date("D:20181309142501"));
+        assert_eq!(None, parse_pdf_date("March 9, 2018"));
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn creation_date_is_read() {
+        let tmpdir = tempfile::TempDir::new().unwrap();
+        let dated = tmpdir.path().join("scan.pdf");
+        std::fs::write(&dated, pdf_with_creation_date("D:20180309142501Z").unwrap()).unwrap();
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2018, 3, 9),
+            read_pdf_date(dated.to_str().unwrap())
+        );
+
+        let undated = tmpdir.path().join("undated.pdf");
+        std::fs::write(&undated, b"%PDF-1.5\n%%EOF\n").unwrap();
+        assert_eq!(None, read_pdf_date(undated.to_str().unwrap()));
+    }
+}
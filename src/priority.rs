@@ -0,0 +1,65 @@
+use std::io;
+
+/// Niceness of a low priority run, like `nice -n 10`
+#[cfg(target_os = "linux")]
+const NICENESS: i32 = 10;
+
+/// The lowest level of the best-effort I/O class, like `ionice -c 2 -n 7`.
+/// Unlike the idle class it still makes progress on a busy disk.
+#[cfg(target_os = "linux")]
+const IOPRIO_BEST_EFFORT_LOWEST: i32 = (2 << 13) | 7;
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: i32 = 1;
+
+// Lower the CPU and I/O priority of the calling thread. Threads started
+// afterwards inherit it, so this has to run before the scan threads exist.
+#[cfg(target_os = "linux")]
+pub fn lower() -> io::Result<()> {
+    use nix::errno::Errno;
+    use nix::libc;
+
+    // SAFETY: both calls only change scheduling attributes of this thread
+    Errno::result(unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICENESS) })?;
+    Errno::result(unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_BEST_EFFORT_LOWEST,
+        )
+    })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn lower() -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn lowers_priority_of_new_threads() {
+        use nix::libc;
+
+        // in a thread of its own, so the other tests keep their priority
+        let (niceness, ioprio) = std::thread::spawn(|| {
+            lower().unwrap();
+            std::thread::spawn(|| unsafe {
+                (
+                    libc::getpriority(libc::PRIO_PROCESS, 0),
+                    libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0),
+                )
+            })
+            .join()
+            .unwrap()
+        })
+        .join()
+        .unwrap();
+        assert!(niceness >= NICENESS);
+        assert_eq!(IOPRIO_BEST_EFFORT_LOWEST as libc::c_long, ioprio);
+    }
+}
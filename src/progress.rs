@@ -0,0 +1,125 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crate::human_bytes;
+
+/// Progress of the copy phase on stderr, shown with `--progress-eta`
+pub struct Progress {
+    files_total: usize,
+    bytes_total: u64,
+    files_done: usize,
+    bytes_done: u64,
+    in_flight: Option<u64>,
+    started: Instant,
+    drawn: Option<Instant>,
+    terminal: bool,
+}
+
+impl Progress {
+    pub fn new(files_total: usize, bytes_total: u64) -> Self {
+        Self {
+            files_total,
+            bytes_total,
+            files_done: 0,
+            bytes_done: 0,
+            in_flight: None,
+            started: Instant::now(),
+            drawn: None,
+            terminal: io::stderr().is_terminal(),
+        }
+    }
+
+    // Mark the previous file done and start on one of the given size
+    pub fn next(&mut self, size: u64) {
+        self.complete();
+        self.in_flight = Some(size);
+        // a terminal line is redrawn in place, a log file gets a line now and then
+        let every = if self.terminal {
+            Duration::from_millis(200)
+        } else {
+            Duration::from_secs(10)
+        };
+        if self.drawn.is_none_or(|drawn| drawn.elapsed() >= every) {
+            self.draw();
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.complete();
+        self.draw();
+        if self.terminal {
+            eprintln!();
+        }
+    }
+
+    fn complete(&mut self) {
+        if let Some(size) = self.in_flight.take() {
+            self.files_done += 1;
+            self.bytes_done += size;
+        }
+    }
+
+    fn draw(&mut self) {
+        let line = self.line(self.started.elapsed());
+        if self.terminal {
+            eprint!("\r{}\x1b[K", line);
+            let _ = io::stderr().flush();
+        } else {
+            eprintln!("{}", line);
+        }
+        self.drawn = Some(Instant::now());
+    }
+
+    // Like "12/340 files, 1.2 GB/8.0 GB, 35.2 MB/s, ETA 3m12s", the ETA
+    // assuming the remaining bytes are copied as fast as the ones so far
+    fn line(&self, elapsed: Duration) -> String {
+        let mut line = format!(
+            "{}/{} files, {}/{}",
+            self.files_done,
+            self.files_total,
+            human_bytes(self.bytes_done),
+            human_bytes(self.bytes_total)
+        );
+        let seconds = elapsed.as_secs_f64();
+        if self.bytes_done > 0 && seconds > 0.0 {
+            let throughput = self.bytes_done as f64 / seconds;
+            let remaining = self.bytes_total.saturating_sub(self.bytes_done) as f64 / throughput;
+            line.push_str(&format!(
+                ", {}/s, ETA {}",
+                human_bytes(throughput as u64),
+                duration(remaining as u64)
+            ));
+        }
+        line
+    }
+}
+
+fn duration(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_follows_throughput() {
+        let mut progress = Progress::new(4, 4 * 1024 * 1024);
+        assert_eq!(
+            "0/4 files, 0 B/4.0 MB",
+            progress.line(Duration::from_secs(1))
+        );
+        progress.files_done = 1;
+        progress.bytes_done = 1024 * 1024;
+        assert_eq!(
+            "1/4 files, 1.0 MB/4.0 MB, 512.0 KB/s, ETA 6s",
+            progress.line(Duration::from_secs(2))
+        );
+        assert_eq!("3m05s", duration(185));
+        assert_eq!("2h01m", duration(7260));
+    }
+}
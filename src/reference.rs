@@ -0,0 +1,117 @@
+use crate::checksum::{self, ChecksumAlgo};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Index every file below the reference root by the hash of its content, with
+// its path relative to the root. Identical copies share a hash.
+pub fn index(root: &Path, algo: ChecksumAlgo) -> io::Result<HashMap<String, Vec<PathBuf>>> {
+    let mut index: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(folder) = pending.pop() {
+        for entry in fs::read_dir(&folder)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                index
+                    .entry(checksum::hex_digest(&path, algo)?)
+                    .or_default()
+                    .push(relative);
+            }
+        }
+    }
+    for paths in index.values_mut() {
+        paths.sort();
+    }
+    Ok(index)
+}
+
+// Compare the planned target of every source with where the reference keeps
+// the same content. Only the differences are listed, followed by the totals.
+pub fn compare(
+    planned: &[(String, PathBuf)],
+    reference: &HashMap<String, Vec<PathBuf>>,
+    algo: ChecksumAlgo,
+) -> io::Result<String> {
+    let mut out = String::new();
+    let (mut matching, mut elsewhere, mut missing) = (0, 0, 0);
+    for (source, target) in planned {
+        let hash = checksum::hex_digest(Path::new(source), algo)?;
+        match reference.get(&hash) {
+            Some(paths) if paths.contains(target) => matching += 1,
+            Some(paths) => {
+                elsewhere += 1;
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                let _ = writeln!(
+                    out,
+                    "differs  {}: planned {}, reference has {}",
+                    source,
+                    target.display(),
+                    paths.join(", ")
+                );
+            }
+            None => {
+                missing += 1;
+                let _ = writeln!(
+                    out,
+                    "missing  {}: planned {}, not in the reference",
+                    source,
+                    target.display()
+                );
+            }
+        }
+    }
+    let _ = writeln!(
+        out,
+        "{} of {} files match the reference, {} differ, {} are missing",
+        matching,
+        planned.len(),
+        elsewhere,
+        missing
+    );
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn differences_are_listed() {
+        let tmpdir = TempDir::new().unwrap();
+        let reference = tmpdir.path().join("reference");
+        fs::create_dir_all(reference.join("2021/01/30")).unwrap();
+        fs::create_dir_all(reference.join("misc")).unwrap();
+        fs::write(reference.join("2021/01/30/a.jpg"), b"a").unwrap();
+        fs::write(reference.join("misc/b.jpg"), b"b").unwrap();
+        let sources = ["a.jpg", "b.jpg", "c.jpg"].map(|name| {
+            let path = tmpdir.path().join(name);
+            fs::write(&path, &name.as_bytes()[..1]).unwrap();
+            path.to_str().unwrap().to_string()
+        });
+        let planned = vec![
+            (sources[0].clone(), PathBuf::from("2021/01/30/a.jpg")),
+            (sources[1].clone(), PathBuf::from("2021/01/31/b.jpg")),
+            (sources[2].clone(), PathBuf::from("2021/02/01/c.jpg")),
+        ];
+
+        let index = index(&reference, ChecksumAlgo::Sha256).unwrap();
+        let report = compare(&planned, &index, ChecksumAlgo::Sha256).unwrap();
+        assert_eq!(
+            format!(
+                "differs  {}: planned 2021/01/31/b.jpg, reference has {}\n\
+                 missing  {}: planned 2021/02/01/c.jpg, not in the reference\n\
+                 1 of 3 files match the reference, 1 differ, 1 are missing\n",
+                sources[1],
+                Path::new("misc/b.jpg").display(),
+                sources[2]
+            ),
+            report
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{exif_capture_time, Strategy};
+use chrono::{DateTime, Local, NaiveDateTime};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The capture time of a file by the first strategy that knows one. The video,
+// pdf, burst and directory strategies only yield dates, so they are passed over.
+pub fn capture_time(filename: &str, order: &[Strategy], mmap: bool) -> Option<NaiveDateTime> {
+    order
+        .iter()
+        .find_map(|strategy| strategy_time(filename, *strategy, mmap))
+}
+
+// The capture time of a file by one strategy, if it knows one
+pub fn strategy_time(filename: &str, strategy: Strategy, mmap: bool) -> Option<NaiveDateTime> {
+    match strategy {
+        Strategy::Filename => filename_time(filename),
+        Strategy::Exif => exif_capture_time(filename, mmap),
+        Strategy::Mtime => modified_time(filename),
+        Strategy::Video | Strategy::Pdf | Strategy::Burst | Strategy::Directory => None,
+    }
+}
+
+// The normalized name for a file taken at the given time, e.g. 20210130_142501.jpg
+pub fn new_name(path: &Path, time: NaiveDateTime) -> PathBuf {
+    let mut name = time.format("%Y%m%d_%H%M%S").to_string();
+    if let Some(ext) = path.extension() {
+        name.push('.');
+        name.push_str(&ext.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+// Times in smartphone names, or in names this already normalized
+pub fn filename_time(filename: &str) -> Option<NaiveDateTime> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?:IMG_|VID_)?(?P<time>\d{8}_\d{6})").unwrap();
+    };
+    let name = Path::new(filename).file_name()?.to_str()?;
+    NaiveDateTime::parse_from_str(&RE.captures(name)?["time"], "%Y%m%d_%H%M%S").ok()
+}
+
+fn modified_time(filename: &str) -> Option<NaiveDateTime> {
+    let modified = fs::metadata(filename).and_then(|m| m.modified()).ok()?;
+    let datetime: DateTime<Local> = modified.into();
+    Some(datetime.naive_local())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{ascii, jpeg_with_exif};
+    use chrono::NaiveDate;
+    use exif::Tag;
+    use tempfile::TempDir;
+
+    fn time(h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2021, 1, 30)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_new_name() {
+        assert_eq!(
+            PathBuf::from("/photos/trip/20210130_142501.JPG"),
+            new_name(Path::new("/photos/trip/DSC_0001.JPG"), time(14, 25, 1))
+        );
+    }
+
+    #[test]
+    fn time_from_exif_before_filename() {
+        let tmpdir = TempDir::new().unwrap();
+        let image = tmpdir.path().join("IMG_20210130_000001.jpg");
+        fs::write(
+            &image,
+            jpeg_with_exif(&[ascii(Tag::DateTimeOriginal, "2021:01:30 14:25:01")]),
+        )
+        .unwrap();
+        let image = image.to_str().unwrap();
+
+        assert_eq!(
+            Some(time(14, 25, 1)),
+            capture_time(image, &[Strategy::Exif, Strategy::Filename], false)
+        );
+        assert_eq!(
+            Some(time(0, 0, 1)),
+            capture_time(image, &[Strategy::Filename, Strategy::Exif], false)
+        );
+        assert_eq!(None, capture_time(image, &[Strategy::Directory], false));
+    }
+
+    #[test]
+    fn time_from_later_exif_dates() {
+        let tmpdir = TempDir::new().unwrap();
+        let image = tmpdir.path().join("edited.jpg");
+        fs::write(
+            &image,
+            jpeg_with_exif(&[
+                ascii(Tag::DateTime, "2023:03:03 12:00:00"),
+                ascii(Tag::DateTimeDigitized, "2021:01:30 14:25:01"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(time(14, 25, 1)),
+            capture_time(image.to_str().unwrap(), &[Strategy::Exif], false)
+        );
+    }
+}
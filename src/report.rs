@@ -0,0 +1,205 @@
+use chrono::Local;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::DateSource;
+
+/// Version of the report structure, bump whenever the format changes
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Machine readable record of a run, written with `--report`
+#[derive(Debug, Serialize)]
+pub struct Report {
+    schema_version: u32,
+    dry_run: bool,
+    files: Vec<ReportEntry>,
+}
+
+/// Aggregate totals of a run, printed with `--summary-json`
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct CopySummary {
+    pub found: usize,
+    pub copied: usize,
+    pub skipped: usize,
+    /// Files that already are at their target, e.g. when re-sorting a sorted library
+    pub in_place: usize,
+    pub errored: usize,
+    pub bytes: u64,
+    pub elapsed_secs: f64,
+    /// Whether the run was stopped before all files were copied
+    pub interrupted: bool,
+    /// Copies that no longer matched their source in the final pass of --verify-all
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_mismatches: Option<usize>,
+}
+
+/// A line of the cumulative `--run-log`, the totals of one run and when it ended
+#[derive(Debug, Serialize)]
+struct RunLogLine<'a> {
+    date: String,
+    dry_run: bool,
+    #[serde(flatten)]
+    summary: &'a CopySummary,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportEntry {
+    source: String,
+    target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_source: Option<DateSource>,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Planned,
+    Copied,
+    Skipped,
+    InPlace,
+    Error,
+}
+
+impl Report {
+    pub fn new(dry_run: bool) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            dry_run,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        source: &str,
+        target: &str,
+        date_source: Option<DateSource>,
+        status: Status,
+    ) {
+        self.files.push(ReportEntry {
+            source: source.to_string(),
+            target: target.to_string(),
+            date_source,
+            status,
+            error: None,
+        });
+    }
+
+    pub fn add_error(
+        &mut self,
+        source: &str,
+        target: &str,
+        date_source: Option<DateSource>,
+        error: String,
+    ) {
+        self.files.push(ReportEntry {
+            source: source.to_string(),
+            target: target.to_string(),
+            date_source,
+            status: Status::Error,
+            error: Some(error),
+        });
+    }
+
+    // Write the report as pretty printed JSON, files sorted by source
+    pub fn write(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.files.sort_by(|a, b| a.source.cmp(&b.source));
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        info!("Wrote report to {}", path.display());
+        Ok(())
+    }
+}
+
+// Append the totals of a run as a JSON line, so the log grows across runs
+pub fn append_run_log(
+    path: &Path,
+    summary: &CopySummary,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    let line = RunLogLine {
+        date: Local::now().to_rfc3339(),
+        dry_run,
+        summary,
+    };
+    let mut log = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(log, "{}", serde_json::to_string(&line)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_serializes_all_totals() {
+        let summary = CopySummary {
+            found: 3,
+            copied: 1,
+            skipped: 1,
+            in_place: 0,
+            errored: 1,
+            bytes: 42,
+            elapsed_secs: 0.5,
+            interrupted: false,
+            verify_mismatches: None,
+        };
+        assert_eq!(
+            r#"{"found":3,"copied":1,"skipped":1,"in_place":0,"errored":1,"bytes":42,"elapsed_secs":0.5,"interrupted":false}"#,
+            serde_json::to_string(&summary).unwrap()
+        );
+    }
+
+    #[test]
+    fn run_log_appends_lines() {
+        let tmpdir = tempfile::TempDir::new().unwrap();
+        let path = tmpdir.path().join("runs.jsonl");
+        let summary = CopySummary {
+            found: 2,
+            copied: 2,
+            ..Default::default()
+        };
+        append_run_log(&path, &summary, false).unwrap();
+        append_run_log(&path, &summary, true).unwrap();
+
+        let log = fs::read_to_string(path).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(2, lines[0]["copied"]);
+        assert_eq!(false, lines[0]["dry_run"]);
+        assert_eq!(true, lines[1]["dry_run"]);
+        assert!(chrono::DateTime::parse_from_rfc3339(lines[1]["date"].as_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn report_contains_schema_version() {
+        let mut report = Report::new(true);
+        report.add(
+            "a.jpg",
+            "2022/01/01/a.jpg",
+            Some(DateSource::Exif),
+            Status::Planned,
+        );
+        report.add_error("b.jpg", "2022/01/01/b.jpg", None, String::from("boom"));
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            SCHEMA_VERSION,
+            json["schema_version"].as_u64().unwrap() as u32
+        );
+        assert_eq!(true, json["dry_run"]);
+        assert_eq!("planned", json["files"][0]["status"]);
+        assert_eq!("exif", json["files"][0]["date_source"]);
+        assert!(json["files"][1].get("date_source").is_none());
+        assert!(json["files"][0].get("error").is_none());
+        assert_eq!("boom", json["files"][1]["error"]);
+    }
+}
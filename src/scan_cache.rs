@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::{DateSource, MediaEntry};
+
+/// Dates found by a previous scan, written with `--scan-cache`. They are
+/// reused for files whose size and modification time did not change, as
+/// long as the settings that pick the date are the same.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    key: String,
+    files: BTreeMap<String, CachedDate>,
+    #[serde(skip)]
+    previous: BTreeMap<String, CachedDate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedDate {
+    size: u64,
+    modified_ns: u64,
+    target: String,
+    date_source: Option<DateSource>,
+}
+
+/// Size and modification time of a file, which invalidate its cached date
+pub type Stamp = (u64, u64);
+
+pub fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), u64::try_from(modified.as_nanos()).ok()?))
+}
+
+impl CachedDate {
+    pub fn new((size, modified_ns): Stamp, entry: &MediaEntry) -> Self {
+        Self {
+            size,
+            modified_ns,
+            target: entry.target.clone(),
+            date_source: entry.date_source,
+        }
+    }
+}
+
+impl ScanCache {
+    // Load the cache of a previous run, starting fresh if there is none, it is
+    // unreadable or it was written with other date settings
+    pub fn load(path: &Path, key: &str) -> Self {
+        let mut cache = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable scan cache {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        if cache.key != key && !cache.files.is_empty() {
+            info!("Date settings changed, rescanning instead of using the scan cache");
+            cache.files.clear();
+        }
+        Self {
+            key: key.to_string(),
+            files: BTreeMap::new(),
+            previous: cache.files,
+        }
+    }
+
+    // The date found by the last run, if the file is unchanged since
+    pub fn get(&self, source: &str, (size, modified_ns): Stamp) -> Option<MediaEntry> {
+        self.previous
+            .get(source)
+            .filter(|cached| cached.size == size && cached.modified_ns == modified_ns)
+            .map(|cached| MediaEntry {
+                target: cached.target.clone(),
+                date_source: cached.date_source,
+                date: Some(crate::date_folders(&cached.target)),
+            })
+    }
+
+    pub fn insert(&mut self, source: &str, cached: CachedDate) {
+        self.files.insert(source.to_string(), cached);
+    }
+
+    // Write the dates of all files scanned in this run
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        debug!("Wrote {} dates to {}", self.files.len(), path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn changed_files_and_settings_invalidate() {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("cache.json");
+        let entry = MediaEntry {
+            target: String::from("2021/01/30/a.jpg"),
+            date_source: Some(DateSource::Exif),
+            date: Some(String::from("2021/01/30")),
+        };
+        let mut cache = ScanCache::load(&path, "exif");
+        cache.insert("a.jpg", CachedDate::new((10, 42), &entry));
+        cache.save(&path).unwrap();
+
+        let cache = ScanCache::load(&path, "exif");
+        assert_eq!(Some(entry), cache.get("a.jpg", (10, 42)));
+        assert_eq!(None, cache.get("a.jpg", (10, 43)));
+        assert_eq!(None, cache.get("a.jpg", (11, 42)));
+        assert_eq!(None, cache.get("b.jpg", (10, 42)));
+        assert_eq!(None, ScanCache::load(&path, "mtime").get("a.jpg", (10, 42)));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::exif_date::exif_segment;
+use crate::{pdf, Options, Strategy};
+use chrono::{Local, TimeZone};
+use exif::{Field, In, Tag, Value};
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tempfile::TempDir;
+
+// Run every date strategy against a sample file built in a temporary folder
+// and print whether it found the expected date
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let tmpdir = TempDir::new()?;
+    let mut failed = 0;
+    for strategy in Strategy::DEFAULT_ORDER {
+        if strategy == Strategy::Pdf && !pdf::is_supported() {
+            println!("skip  {:<10} needs a build with the pdf feature", "pdf");
+            continue;
+        }
+        let (sample, expected) = sample(tmpdir.path(), strategy)?;
+        let found = strategy.target_path(sample.to_str().unwrap(), &Options::default());
+        let name = format!("{:?}", strategy).to_lowercase();
+        match found {
+            Some(target) if target.starts_with(expected) => {
+                println!("ok    {:<10} {}", name, target);
+            }
+            found => {
+                failed += 1;
+                println!(
+                    "FAIL  {:<10} expected {}, found {}",
+                    name,
+                    expected,
+                    found.as_deref().unwrap_or("no date")
+                );
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} strategies failed",
+            failed,
+            Strategy::DEFAULT_ORDER.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// Write the sample for a strategy, returning it with the date folders it should get
+fn sample(folder: &Path, strategy: Strategy) -> Result<(PathBuf, &'static str), Box<dyn Error>> {
+    let sample = match strategy {
+        Strategy::Filename => {
+            let path = folder.join("IMG_20210130_000001.jpg");
+            File::create(&path)?;
+            (path, "2021/01/30/")
+        }
+        Strategy::Exif => {
+            let path = folder.join("exif.jpg");
+            let date = ascii(Tag::DateTimeOriginal, "2019:07:04 12:00:00");
+            fs::write(&path, jpeg_with_exif(&[date]))?;
+            (path, "2019/07/04/")
+        }
+        Strategy::Video => {
+            let path = folder.join("clip.mov");
+            fs::write(&path, movie_with_date("2023-01-14T12:00:00"))?;
+            (path, "2023/01/14/")
+        }
+        Strategy::Pdf => {
+            let path = folder.join("scan.pdf");
+            fs::write(&path, pdf::pdf_with_creation_date("D:20180309142501")?)?;
+            (path, "2018/03/09/")
+        }
+        Strategy::Burst => {
+            let burst = folder.join("2022-08-21 Burst");
+            fs::create_dir_all(&burst)?;
+            let path = burst.join("001.jpg");
+            File::create(&path)?;
+            (path, "2022/08/21/")
+        }
+        Strategy::Directory => {
+            let scans = folder.join("2005-07 Summer Trip");
+            fs::create_dir_all(&scans)?;
+            let path = scans.join("scan.jpg");
+            File::create(&path)?;
+            (path, "2005/07/")
+        }
+        Strategy::Mtime => {
+            let path = folder.join("mtime.jpg");
+            let noon = Local
+                .with_ymd_and_hms(2020, 6, 15, 12, 0, 0)
+                .single()
+                .ok_or("cannot build the sample modification time")?;
+            File::create(&path)?.set_modified(SystemTime::from(noon))?;
+            (path, "2020/06/15/")
+        }
+    };
+    Ok(sample)
+}
+
+// Build an ASCII field for the primary image
+pub fn ascii(tag: Tag, value: &str) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    }
+}
+
+// Build a minimal JPEG that only carries the given exif fields
+pub fn jpeg_with_exif(fields: &[Field]) -> Vec<u8> {
+    let mut jpeg = vec![0xff, 0xd8];
+    jpeg.extend(exif_segment(fields).expect("exif fields fit into a segment"));
+    jpeg.extend_from_slice(&[0xff, 0xd9]);
+    jpeg
+}
+
+fn atom(kind: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut atom = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    atom.extend_from_slice(kind);
+    atom.extend_from_slice(payload);
+    atom
+}
+
+// Build a minimal QuickTime movie with a udta/©day creation date
+fn movie_with_date(date: &str) -> Vec<u8> {
+    let mut day = (date.len() as u16).to_be_bytes().to_vec();
+    day.extend_from_slice(&[0x55, 0xc4]);
+    day.extend_from_slice(date.as_bytes());
+    let mut movie = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+    movie.extend(atom(b"mdat", &[0; 16]));
+    movie.extend(atom(b"moov", &atom(b"udta", &atom(b"\xa9day", &day))));
+    movie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_strategies_pass() {
+        assert!(run().is_ok());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::Strategy;
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+// Describe how many of the sampled files each strategy dates and suggest a
+// strategy order, most productive first. The modification time always
+// matches, so it stays last as the fallback.
+pub fn suggestion(sampled: usize, matches: &[(Strategy, usize)]) -> String {
+    let mut out = format!("Sampled {} files\n", sampled);
+    for (strategy, count) in matches {
+        let _ = writeln!(
+            out,
+            "  {:<10} {:>3}%",
+            strategy.to_string(),
+            percent(*count, sampled)
+        );
+    }
+    let mut productive: Vec<&(Strategy, usize)> = matches
+        .iter()
+        .filter(|(strategy, count)| *strategy != Strategy::Mtime && *count > 0)
+        .collect();
+    productive.sort_by_key(|(_, count)| Reverse(*count));
+    let order: Vec<String> = productive
+        .iter()
+        .map(|(strategy, _)| *strategy)
+        .chain([Strategy::Mtime])
+        .map(|strategy| strategy.to_string())
+        .collect();
+    if let Some((best, count)) = productive.first() {
+        let _ = writeln!(
+            out,
+            "{}% are dated by {}; suggest --strategy-order {}",
+            percent(*count, sampled),
+            best,
+            order.join(",")
+        );
+    } else {
+        let _ = writeln!(
+            out,
+            "Only the modification time dates these files; suggest --strategy-order mtime"
+        );
+    }
+    out
+}
+
+fn percent(count: usize, total: usize) -> usize {
+    (count * 100).checked_div(total).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_productive_strategy_first() {
+        let matches = [
+            (Strategy::Filename, 3),
+            (Strategy::Exif, 9),
+            (Strategy::Video, 0),
+            (Strategy::Burst, 0),
+            (Strategy::Directory, 3),
+            (Strategy::Mtime, 10),
+        ];
+        let suggestion = suggestion(10, &matches);
+        assert!(suggestion.contains("  exif        90%\n"));
+        assert!(suggestion.ends_with(
+            "90% are dated by exif; suggest --strategy-order exif,filename,directory,mtime\n"
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_mtime() {
+        let suggestion = suggestion(0, &[(Strategy::Exif, 0), (Strategy::Mtime, 0)]);
+        assert!(suggestion.ends_with("suggest --strategy-order mtime\n"));
+    }
+}
@@ -0,0 +1 @@
+pub use crate::selftest::{ascii, jpeg_with_exif};
@@ -0,0 +1,77 @@
+use crate::is_media_file;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The name counted for files without an extension
+const NO_EXTENSION: &str = "(none)";
+
+// Count the lowercase extensions of the files below a folder that are not
+// sorted as media
+pub fn count(folder: &Path, recursive: bool) -> io::Result<HashMap<String, usize>> {
+    let mut counts = HashMap::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(folder) = pending.pop() {
+        for entry in fs::read_dir(&folder)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if path.is_file() && !is_media_file(&path) {
+                let extension = path
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().to_lowercase())
+                    .unwrap_or_else(|| String::from(NO_EXTENSION));
+                *counts.entry(extension).or_default() += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+// One line per extension, the most frequent first
+pub fn report(counts: &HashMap<String, usize>) -> String {
+    let mut counts: Vec<(&String, &usize)> = counts.iter().collect();
+    counts.sort_by_key(|(extension, count)| (Reverse(**count), *extension));
+    let mut out = format!(
+        "{} files with {} unknown extensions\n",
+        counts.iter().map(|(_, count)| **count).sum::<usize>(),
+        counts.len()
+    );
+    for (extension, count) in counts {
+        let _ = writeln!(out, "  {:<10} {:>6}", extension, count);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn unknown_extensions_by_count() {
+        let tmpdir = TempDir::new().unwrap();
+        let nested = tmpdir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        for name in ["a.jpg", "b.GIF", "c.gif", "d.txt", "README"] {
+            fs::File::create(tmpdir.path().join(name)).unwrap();
+        }
+        fs::File::create(nested.join("e.gif")).unwrap();
+        fs::File::create(nested.join("f.txt")).unwrap();
+
+        let counts = count(tmpdir.path(), true).unwrap();
+        assert_eq!(
+            "6 files with 3 unknown extensions\n  \
+             gif             3\n  \
+             txt             2\n  \
+             (none)          1\n",
+            report(&counts)
+        );
+        assert_eq!(Some(&1), count(tmpdir.path(), false).unwrap().get("txt"));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::checksum::{self, ChecksumAlgo};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// A copy together with the hash its source had when it was copied
+pub type Recorded = (PathBuf, Vec<u8>);
+
+// Hash every copy again and list the ones that no longer match their source,
+// or cannot be read anymore, with the reason
+pub fn mismatches(recorded: &[Recorded], algo: ChecksumAlgo) -> Vec<(PathBuf, String)> {
+    recorded
+        .iter()
+        .filter_map(|(target, hash)| match checksum::file_digest(target, algo) {
+            Ok(current) if current == *hash => None,
+            Ok(_) => Some((
+                target.clone(),
+                String::from("content differs from its source"),
+            )),
+            Err(e) => Some((target.clone(), format!("cannot be read: {}", e))),
+        })
+        .collect()
+}
+
+// The PASS or FAIL line of the final pass, followed by the mismatched files
+pub fn summary(checked: usize, mismatches: &[(PathBuf, String)]) -> String {
+    if mismatches.is_empty() {
+        return format!("Verify all: PASS, {} copies match their sources\n", checked);
+    }
+    let mut out = format!(
+        "Verify all: FAIL, {} of {} copies do not match their sources\n",
+        mismatches.len(),
+        checked
+    );
+    for (target, reason) in mismatches {
+        let _ = writeln!(out, "  {}: {}", target.display(), reason);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn changed_and_missing_copies_fail() {
+        let tmpdir = TempDir::new().unwrap();
+        let good = tmpdir.path().join("good.jpg");
+        let bad = tmpdir.path().join("bad.jpg");
+        let gone = tmpdir.path().join("gone.jpg");
+        for path in [&good, &bad, &gone] {
+            fs::write(path, b"original").unwrap();
+        }
+        let recorded: Vec<Recorded> = [&good, &bad, &gone]
+            .iter()
+            .map(|path| {
+                let hash = checksum::file_digest(path, ChecksumAlgo::Sha256).unwrap();
+                (path.to_path_buf(), hash)
+            })
+            .collect();
+        assert_eq!(
+            "Verify all: PASS, 3 copies match their sources\n",
+            summary(3, &mismatches(&recorded, ChecksumAlgo::Sha256))
+        );
+
+        fs::write(&bad, b"origina!").unwrap();
+        fs::remove_file(&gone).unwrap();
+        let mismatches = mismatches(&recorded, ChecksumAlgo::Sha256);
+        assert_eq!(
+            vec![bad.clone(), gone.clone()],
+            mismatches
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>()
+        );
+        assert!(summary(3, &mismatches).starts_with(&format!(
+            "Verify all: FAIL, 2 of 3 copies do not match their sources\n  {}: content differs",
+            bad.display()
+        )));
+    }
+}
@@ -0,0 +1,308 @@
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// Refuse to load absurdly large moov boxes into memory
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+// Seconds between the QuickTime epoch (1904-01-01) and the Unix epoch
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+// Read the capture date from the metadata of a QuickTime/MP4 video. The `©day`
+// user data atom holds the local date including a timezone, so it is preferred
+// over the mvhd creation time, which is UTC and often left unset.
+pub fn read_video_date(filename: &str) -> Option<String> {
+    let moov = match read_moov(Path::new(filename)) {
+        Ok(moov) => moov?,
+        Err(e) => {
+            debug!("Could not read video metadata of {}: {}", filename, e);
+            return None;
+        }
+    };
+    quicktime_day(&moov).or_else(|| mvhd_creation_date(&moov))
+}
+
+// The displayed width and height of the first video track, read from its
+// tkhd box. The track matrix turns phone videos recorded upright by 90
+// degrees, in which case width and height are swapped.
+pub fn read_video_dimensions(filename: &str) -> Option<(u32, u32)> {
+    let moov = match read_moov(Path::new(filename)) {
+        Ok(moov) => moov?,
+        Err(e) => {
+            debug!("Could not read video metadata of {}: {}", filename, e);
+            return None;
+        }
+    };
+    let dimensions = boxes(&moov)
+        .filter(|(kind, _)| *kind == b"trak")
+        .find_map(|(_, trak)| tkhd_dimensions(child(trak, b"tkhd")?));
+    dimensions
+}
+
+// Width and height are 16.16 fixed point numbers at the end of tkhd, after
+// the 3x3 matrix whose first two entries tell a rotation by 90 degrees
+fn tkhd_dimensions(tkhd: &[u8]) -> Option<(u32, u32)> {
+    let fixed = |offset: usize| {
+        tkhd.get(offset..offset + 4)
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+    let matrix = match tkhd.first()? {
+        0 => 40,
+        1 => 52,
+        _ => return None,
+    };
+    let width = fixed(matrix + 36)? >> 16;
+    let height = fixed(matrix + 40)? >> 16;
+    if width == 0 || height == 0 {
+        // audio tracks have no size
+        return None;
+    }
+    if fixed(matrix)? == 0 && fixed(matrix + 4)? != 0 {
+        Some((height, width))
+    } else {
+        Some((width, height))
+    }
+}
+
+// Find the top-level moov box and return its payload
+fn read_moov(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut offset = 0;
+    while len.saturating_sub(offset) >= 8 {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        let mut size = u32::from_be_bytes(header[..4].try_into().unwrap()) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            let mut large = [0; 8];
+            file.read_exact(&mut large)?;
+            size = u64::from_be_bytes(large);
+            header_len = 16;
+        } else if size == 0 {
+            size = len - offset;
+        }
+        if size < header_len {
+            return Ok(None);
+        }
+        if &header[4..] == b"moov" {
+            if size > MAX_MOOV_SIZE {
+                return Ok(None);
+            }
+            let mut moov = vec![0; (size - header_len) as usize];
+            file.read_exact(&mut moov)?;
+            return Ok(Some(moov));
+        }
+        // the size comes from the file, a crafted one must not overflow
+        match offset.checked_add(size) {
+            Some(next) => offset = next,
+            None => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+// Iterate over the (type, payload) pairs of the boxes in a container payload
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        if data.len() < 8 {
+            return None;
+        }
+        let size = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+        let size = if size == 0 { data.len() } else { size };
+        if size < 8 || size > data.len() {
+            return None;
+        }
+        let (current, rest) = data.split_at(size);
+        data = rest;
+        Some((&current[4..8], &current[8..]))
+    })
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data)
+        .find(|(k, _)| *k == kind)
+        .map(|(_, payload)| payload)
+}
+
+// The meta box has a version and flags in MP4 files, but not in QuickTime
+fn meta_children(meta: &[u8]) -> &[u8] {
+    if meta.len() >= 8 && &meta[4..8] == b"hdlr" {
+        meta
+    } else {
+        meta.get(4..).unwrap_or_default()
+    }
+}
+
+// Read `©day` from moov/udta, either as QuickTime text or as iTunes style metadata
+fn quicktime_day(moov: &[u8]) -> Option<String> {
+    const DAY: &[u8] = b"\xa9day";
+    let udta = child(moov, b"udta")?;
+    let text = match child(udta, DAY) {
+        // 16 bit text length and 16 bit language code before the text
+        Some(atom) => atom.get(4..)?,
+        None => {
+            let ilst = child(meta_children(child(udta, b"meta")?), b"ilst")?;
+            // 32 bit type and 32 bit locale before the text
+            child(child(ilst, DAY)?, b"data")?.get(8..)?
+        }
+    };
+    parse_iso_date(&String::from_utf8_lossy(text))
+}
+
+fn parse_iso_date(text: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^\s*(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})").unwrap();
+    };
+    RE.captures(text)
+        .map(|cap| format!("{}/{}/{}", &cap["y"], &cap["m"], &cap["d"]))
+}
+
+// The mvhd creation time in seconds since 1904 UTC, zero when unset
+fn mvhd_creation_date(moov: &[u8]) -> Option<String> {
+    let mvhd = child(moov, b"mvhd")?;
+    let seconds = match mvhd.first()? {
+        0 => i64::from(u32::from_be_bytes(mvhd.get(4..8)?.try_into().ok()?)),
+        1 => i64::try_from(u64::from_be_bytes(mvhd.get(4..12)?.try_into().ok()?)).ok()?,
+        _ => return None,
+    };
+    if seconds == 0 {
+        return None;
+    }
+    let created = DateTime::from_timestamp(seconds.checked_sub(QUICKTIME_EPOCH_OFFSET)?, 0)?;
+    Some(created.with_timezone(&Local).format("%Y/%m/%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn atom(kind: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut atom = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend_from_slice(payload);
+        atom
+    }
+
+    fn mvhd(seconds: u32) -> Vec<u8> {
+        let mut payload = vec![0; 4];
+        payload.extend_from_slice(&seconds.to_be_bytes());
+        payload.extend_from_slice(&[0; 92]);
+        atom(b"mvhd", &payload)
+    }
+
+    fn day(text: &str) -> Vec<u8> {
+        let mut payload = (text.len() as u16).to_be_bytes().to_vec();
+        payload.extend_from_slice(&[0x55, 0xc4]);
+        payload.extend_from_slice(text.as_bytes());
+        atom(b"udta", &atom(b"\xa9day", &payload))
+    }
+
+    // Build a minimal QuickTime movie with the given moov children
+    fn movie(children: &[Vec<u8>]) -> Vec<u8> {
+        let mut movie = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+        movie.extend(atom(b"mdat", &[0; 16]));
+        movie.extend(atom(b"moov", &children.concat()));
+        movie
+    }
+
+    // A video track header with the given matrix start and 16.16 size
+    fn tkhd(a: u32, b: u32, width: u32, height: u32) -> Vec<u8> {
+        let mut payload = vec![0; 40];
+        payload.extend_from_slice(&a.to_be_bytes());
+        payload.extend_from_slice(&b.to_be_bytes());
+        payload.extend_from_slice(&[0; 28]);
+        payload.extend_from_slice(&(width << 16).to_be_bytes());
+        payload.extend_from_slice(&(height << 16).to_be_bytes());
+        atom(b"trak", &atom(b"tkhd", &payload))
+    }
+
+    fn video_date(tmpdir: &TempDir, content: &[u8]) -> Option<String> {
+        let video = tmpdir.path().join("clip.mov");
+        fs::write(&video, content).unwrap();
+        read_video_date(video.to_str().unwrap())
+    }
+
+    // 2023-01-15 12:00:00 UTC in seconds since 1904
+    const NOON: u32 = 3_756_628_800;
+
+    #[test]
+    fn quicktime_day_is_preferred_over_mvhd() {
+        let tmpdir = TempDir::new().unwrap();
+        let content = movie(&[mvhd(NOON), day("2023-01-14T23:30:00-0800")]);
+        assert_eq!(
+            Some(String::from("2023/01/14")),
+            video_date(&tmpdir, &content)
+        );
+    }
+
+    #[test]
+    fn out_of_range_mvhd_times_are_ignored() {
+        for seconds in [u64::MAX, 1 << 63, i64::MAX as u64] {
+            let mut payload = vec![1, 0, 0, 0];
+            payload.extend_from_slice(&seconds.to_be_bytes());
+            payload.extend_from_slice(&[0; 96]);
+            assert_eq!(None, mvhd_creation_date(&atom(b"mvhd", &payload)));
+        }
+    }
+
+    #[test]
+    fn oversized_boxes_are_not_followed() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut content = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+        // a 64 bit largesize that runs past the end of any offset
+        content.extend_from_slice(&1u32.to_be_bytes());
+        content.extend_from_slice(b"mdat");
+        content.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(None, video_date(&tmpdir, &content));
+    }
+
+    #[test]
+    fn mvhd_is_used_without_quicktime_day() {
+        let tmpdir = TempDir::new().unwrap();
+        assert_eq!(
+            Some(String::from("2023/01/15")),
+            video_date(&tmpdir, &movie(&[mvhd(NOON)]))
+        );
+        assert_eq!(None, video_date(&tmpdir, &movie(&[mvhd(0)])));
+        assert_eq!(None, video_date(&tmpdir, b"not a movie"));
+    }
+
+    #[test]
+    fn video_dimensions_follow_rotation() {
+        let tmpdir = TempDir::new().unwrap();
+        let dimensions = |children: &[Vec<u8>]| {
+            let video = tmpdir.path().join("clip.mov");
+            fs::write(&video, movie(children)).unwrap();
+            read_video_dimensions(video.to_str().unwrap())
+        };
+        let audio = tkhd(0x10000, 0, 0, 0);
+        let landscape = tkhd(0x10000, 0, 1920, 1080);
+        let upright = tkhd(0, 0x10000, 1920, 1080);
+
+        assert_eq!(Some((1920, 1080)), dimensions(&[audio.clone(), landscape]));
+        assert_eq!(Some((1080, 1920)), dimensions(&[upright]));
+        assert_eq!(None, dimensions(&[audio]));
+    }
+
+    #[test]
+    fn itunes_style_day_is_read() {
+        let tmpdir = TempDir::new().unwrap();
+        let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend_from_slice(b"2021-07-04");
+        let ilst = atom(b"ilst", &atom(b"\xa9day", &atom(b"data", &data)));
+        let mut meta = vec![0; 4];
+        meta.extend(ilst);
+        let udta = atom(b"udta", &atom(b"meta", &meta));
+        assert_eq!(
+            Some(String::from("2021/07/04")),
+            video_date(&tmpdir, &movie(&[mvhd(NOON), udta]))
+        );
+    }
+}
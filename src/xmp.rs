@@ -0,0 +1,225 @@
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// The XMP properties holding the capture date, in order of preference
+const DATE_PROPERTIES: [&str; 2] = ["exif:DateTimeOriginal", "xmp:CreateDate"];
+
+// How much of a file to search for an XMP packet. JPEG and DNG keep it up
+// front, so a large raw file is not read in full
+const SEARCH_LIMIT: u64 = 256 * 1024;
+
+// Read the capture date from the XMP packet embedded in the start of a file,
+// returned as "yyyy/mm/dd"
+pub fn read_embedded_date(path: &Path) -> Option<String> {
+    find_date(&read_head(path).ok()?)
+}
+
+fn read_head(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    fs::File::open(path)?
+        .take(SEARCH_LIMIT)
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
+
+// Read the capture date from the XMP sidecar next to a file, as written by
+// Lightroom and darktable either as IMG_1.NEF.xmp or IMG_1.xmp
+pub fn read_sidecar_date(path: &Path) -> Option<String> {
+    sidecars(path)
+        .iter()
+        .find(|sidecar| sidecar.is_file())
+        .and_then(|sidecar| read_embedded_date(sidecar))
+}
+
+// The existing sidecars of a source, each with its name next to the target
+pub fn sidecar_targets(source: &Path, target: &Path) -> Vec<(PathBuf, PathBuf)> {
+    sidecars(source)
+        .into_iter()
+        .zip(sidecars(target))
+        .filter(|(sidecar, _)| sidecar.is_file())
+        .collect()
+}
+
+fn sidecars(path: &Path) -> Vec<PathBuf> {
+    let mut full = path.as_os_str().to_owned();
+    full.push(".xmp");
+    let mut sidecars = vec![PathBuf::from(full)];
+    for ext in ["xmp", "XMP"] {
+        sidecars.push(path.with_extension(ext));
+    }
+    sidecars
+}
+
+// The names of the people tagged in the face regions of an image, in order
+pub fn read_face_names(path: &Path) -> Vec<String> {
+    read_head(path)
+        .map(|head| find_face_names(&head))
+        .unwrap_or_default()
+}
+
+// Find the names of MWG regions (mwg-rs:Name) and Windows Photo Gallery
+// regions (MPReg:PersonDisplayName), as attributes or elements
+pub fn find_face_names(data: &[u8]) -> Vec<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r#"(?:mwg-rs:Name|MPReg:PersonDisplayName)\s*(?:=\s*"(?P<attr>[^"]*)"|>(?P<elem>[^<]*)<)"#
+        )
+        .unwrap();
+    }
+    RE.captures_iter(data)
+        .filter_map(|cap| {
+            let name = cap.name("attr").or_else(|| cap.name("elem"))?;
+            let name = unescape(std::str::from_utf8(name.as_bytes()).ok()?.trim());
+            (!name.is_empty()).then_some(name)
+        })
+        .collect()
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Find the capture date in XMP data. Properties can be written either as
+// attributes (xmp:CreateDate="2021-01-30T10:00:00") or as elements
+// (<xmp:CreateDate>2021-01-30T10:00:00</xmp:CreateDate>).
+pub fn find_date(data: &[u8]) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r#"(?P<name>exif:DateTimeOriginal|xmp:CreateDate)\s*(?:=\s*["']|>)\s*(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})"#
+        )
+        .unwrap();
+    }
+    let mut dates: Vec<(usize, String)> = RE
+        .captures_iter(data)
+        .filter_map(|cap| {
+            let priority = DATE_PROPERTIES
+                .iter()
+                .position(|name| name.as_bytes() == &cap["name"])?;
+            let date = format!(
+                "{}/{}/{}",
+                std::str::from_utf8(&cap["y"]).ok()?,
+                std::str::from_utf8(&cap["m"]).ok()?,
+                std::str::from_utf8(&cap["d"]).ok()?
+            );
+            Some((priority, date))
+        })
+        .collect();
+    dates.sort();
+    dates.into_iter().next().map(|(_, date)| date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn date_from_attributes() {
+        let packet = br#"<rdf:Description xmp:CreateDate="2021-01-30T10:00:00"
+            exif:DateTimeOriginal="2021-01-29T23:59:00+01:00"/>"#;
+        assert_eq!(Some("2021/01/29".to_string()), find_date(packet));
+    }
+
+    #[test]
+    fn date_from_elements() {
+        let packet = b"<xmp:CreateDate>2019-07-04T12:00:00</xmp:CreateDate>";
+        assert_eq!(Some("2019/07/04".to_string()), find_date(packet));
+    }
+
+    #[test]
+    fn embedded_date_only_in_the_start_of_a_file() {
+        let tmpdir = TempDir::new().unwrap();
+        let packet = b"<xmp:CreateDate>2019-07-04T12:00:00</xmp:CreateDate>";
+        let raw = tmpdir.path().join("IMG_0001.dng");
+        fs::write(&raw, [&packet[..], &[0; 1024]].concat()).unwrap();
+        assert_eq!(Some("2019/07/04".to_string()), read_embedded_date(&raw));
+
+        fs::write(
+            &raw,
+            [&vec![0; SEARCH_LIMIT as usize][..], &packet[..]].concat(),
+        )
+        .unwrap();
+        assert_eq!(None, read_embedded_date(&raw));
+    }
+
+    #[test]
+    fn sidecar_with_full_name() {
+        let tmpdir = TempDir::new().unwrap();
+        let raw = tmpdir.path().join("DSC_0001.NEF");
+        fs::write(&raw, b"raw").unwrap();
+        assert_eq!(None, read_sidecar_date(&raw));
+
+        fs::write(
+            tmpdir.path().join("DSC_0001.NEF.xmp"),
+            b"<xmp:CreateDate>2018-03-01T09:00:00</xmp:CreateDate>",
+        )
+        .unwrap();
+        assert_eq!(Some("2018/03/01".to_string()), read_sidecar_date(&raw));
+    }
+
+    #[test]
+    fn sidecar_replacing_extension() {
+        let tmpdir = TempDir::new().unwrap();
+        let raw = tmpdir.path().join("IMG_0001.cr2");
+        fs::write(&raw, b"raw").unwrap();
+        fs::write(
+            tmpdir.path().join("IMG_0001.xmp"),
+            br#"<rdf:Description exif:DateTimeOriginal="2017-12-24T18:00:00"/>"#,
+        )
+        .unwrap();
+        assert_eq!(Some("2017/12/24".to_string()), read_sidecar_date(&raw));
+    }
+
+    #[test]
+    fn sidecars_keep_their_naming() {
+        let tmpdir = TempDir::new().unwrap();
+        let raw = tmpdir.path().join("DSC_0001.NEF");
+        fs::write(tmpdir.path().join("DSC_0001.NEF.xmp"), b"").unwrap();
+        fs::write(tmpdir.path().join("DSC_0001.xmp"), b"").unwrap();
+        let target = Path::new("/sorted/DSC_0001_2.NEF");
+        assert_eq!(
+            vec![
+                (
+                    tmpdir.path().join("DSC_0001.NEF.xmp"),
+                    PathBuf::from("/sorted/DSC_0001_2.NEF.xmp")
+                ),
+                (
+                    tmpdir.path().join("DSC_0001.xmp"),
+                    PathBuf::from("/sorted/DSC_0001_2.xmp")
+                ),
+            ],
+            sidecar_targets(&raw, target)
+                .into_iter()
+                .filter(|(sidecar, _)| !sidecar.ends_with("DSC_0001.XMP"))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn face_region_names() {
+        let packet = br#"<mwg-rs:RegionList><rdf:Bag>
+            <rdf:li><rdf:Description mwg-rs:Name="Anna &amp; Co" mwg-rs:Type="Face"/></rdf:li>
+            <rdf:li><rdf:Description><mwg-rs:Name>Ben</mwg-rs:Name></rdf:Description></rdf:li>
+            <rdf:li><rdf:Description mwg-rs:Name="" mwg-rs:Type="Face"/></rdf:li>
+            </rdf:Bag></mwg-rs:RegionList>
+            <MPReg:PersonDisplayName>Carla</MPReg:PersonDisplayName>"#;
+        assert_eq!(vec!["Anna & Co", "Ben", "Carla"], find_face_names(packet));
+        assert!(find_face_names(b"<x:xmpmeta/>").is_empty());
+    }
+
+    #[test]
+    fn no_date() {
+        assert_eq!(
+            None,
+            find_date(b"<xmp:ModifyDate>2019-07-04</xmp:ModifyDate>")
+        );
+        assert_eq!(None, find_date(b"\x00\x01binary"));
+    }
+}